@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX favourite_streams_user_identifier_source;
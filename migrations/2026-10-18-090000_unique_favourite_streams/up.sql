@@ -0,0 +1,10 @@
+-- Your SQL goes here
+DELETE FROM favourite_streams a
+    USING favourite_streams b
+    WHERE a.id > b.id
+    AND a.associated_user = b.associated_user
+    AND a.identifier = b.identifier
+    AND a.source = b.source;
+
+CREATE UNIQUE INDEX favourite_streams_user_identifier_source
+    ON favourite_streams (associated_user, identifier, source);
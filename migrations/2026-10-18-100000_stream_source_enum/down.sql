@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE favourite_streams
+    ALTER COLUMN source TYPE VARCHAR USING source::VARCHAR;
+
+DROP TYPE stream_source;
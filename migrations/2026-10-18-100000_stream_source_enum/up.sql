@@ -0,0 +1,7 @@
+-- Your SQL goes here
+UPDATE favourite_streams SET source = 'Youtube' WHERE source = 'Toutube';
+
+CREATE TYPE stream_source AS ENUM ('Twitch', 'Youtube', 'BeemStream');
+
+ALTER TABLE favourite_streams
+    ALTER COLUMN source TYPE stream_source USING source::stream_source;
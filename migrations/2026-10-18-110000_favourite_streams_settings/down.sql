@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE favourite_streams_settings;
+DROP TYPE favourite_visibility;
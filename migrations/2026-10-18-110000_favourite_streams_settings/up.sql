@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TYPE favourite_visibility AS ENUM ('Private', 'Public', 'Unlisted');
+
+CREATE TABLE favourite_streams_settings (
+    associated_user INT PRIMARY KEY,
+    visibility favourite_visibility NOT NULL DEFAULT 'Private'
+);
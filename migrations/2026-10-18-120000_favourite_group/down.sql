@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE favourite_group_member;
+DROP TABLE favourite_group;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE favourite_group (
+    id SERIAL PRIMARY KEY,
+    associated_user INT NOT NULL,
+    name VARCHAR NOT NULL,
+    UNIQUE (associated_user, name)
+);
+
+CREATE TABLE favourite_group_member (
+    associated_group INT NOT NULL REFERENCES favourite_group(id) ON DELETE CASCADE,
+    associated_favourite INT NOT NULL REFERENCES favourite_streams(id) ON DELETE CASCADE,
+    PRIMARY KEY (associated_group, associated_favourite)
+);
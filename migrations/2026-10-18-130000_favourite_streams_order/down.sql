@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE favourite_streams
+    DROP COLUMN position,
+    DROP COLUMN pinned;
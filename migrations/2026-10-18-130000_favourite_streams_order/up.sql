@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE favourite_streams
+    ADD COLUMN position INT NOT NULL DEFAULT 0,
+    ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE favourite_streams f
+    SET position = ordered.position
+    FROM (
+        SELECT id, ROW_NUMBER() OVER (PARTITION BY associated_user ORDER BY id) - 1 AS position
+        FROM favourite_streams
+    ) ordered
+    WHERE f.id = ordered.id;
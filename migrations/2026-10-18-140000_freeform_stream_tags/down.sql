@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+UPDATE stream_tag SET source_id = '' WHERE source_id IS NULL;
+ALTER TABLE stream_tag ALTER COLUMN source_id SET NOT NULL;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Freeform tags have no Twitch tag_id, legacy rows keep theirs in source_id
+ALTER TABLE stream_tag ALTER COLUMN source_id DROP NOT NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE stream_title
+    DROP COLUMN game_id,
+    DROP COLUMN game_name,
+    DROP COLUMN broadcaster_language;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE stream_title
+    ADD COLUMN game_id VARCHAR,
+    ADD COLUMN game_name VARCHAR,
+    ADD COLUMN broadcaster_language VARCHAR;
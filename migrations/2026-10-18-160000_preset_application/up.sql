@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE preset_application (
+    id SERIAL PRIMARY KEY,
+    associated_user VARCHAR NOT NULL,
+    associated_title INT REFERENCES stream_title (id) ON DELETE SET NULL,
+    reverted_application INT REFERENCES preset_application (id) ON DELETE SET NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    previous_state JSONB NOT NULL,
+    applied_state JSONB NOT NULL,
+    applied BOOLEAN NOT NULL,
+    twitch_status INT
+);
+
+CREATE INDEX preset_application_associated_user_idx
+    ON preset_application (associated_user, applied_at DESC);
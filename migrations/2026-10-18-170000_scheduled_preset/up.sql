@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE scheduled_preset (
+    id SERIAL PRIMARY KEY,
+    associated_user VARCHAR NOT NULL,
+    associated_title INT NOT NULL REFERENCES stream_title (id) ON DELETE CASCADE,
+    access_token VARCHAR NOT NULL,
+    run_at TIMESTAMPTZ NOT NULL,
+    ran_at TIMESTAMPTZ,
+    applied BOOLEAN,
+    twitch_status INT,
+    associated_application INT REFERENCES preset_application (id) ON DELETE SET NULL
+);
+
+CREATE INDEX scheduled_preset_due_idx ON scheduled_preset (run_at) WHERE ran_at IS NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE scheduled_preset ADD COLUMN access_token VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE scheduled_preset ALTER COLUMN access_token DROP DEFAULT;
+
+DROP TABLE twitch_credential;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE twitch_credential (
+    associated_user VARCHAR PRIMARY KEY,
+    access_token VARCHAR NOT NULL,
+    refresh_token VARCHAR NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- schedules now run with the linked credentials instead of the token they were created with
+ALTER TABLE scheduled_preset DROP COLUMN access_token;
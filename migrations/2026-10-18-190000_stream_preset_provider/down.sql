@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE scheduled_preset RENAME COLUMN platform_status TO twitch_status;
+ALTER TABLE preset_application RENAME COLUMN platform_status TO twitch_status;
+
+ALTER TABLE preset_application DROP COLUMN provider;
+
+ALTER TABLE stream_title
+    DROP COLUMN provider,
+    DROP COLUMN description;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE stream_title
+    ADD COLUMN provider stream_source NOT NULL DEFAULT 'Twitch',
+    ADD COLUMN description VARCHAR;
+
+ALTER TABLE preset_application
+    ADD COLUMN provider stream_source NOT NULL DEFAULT 'Twitch';
+
+-- statuses now come from whichever platform the preset was applied to
+ALTER TABLE preset_application RENAME COLUMN twitch_status TO platform_status;
+ALTER TABLE scheduled_preset RENAME COLUMN twitch_status TO platform_status;
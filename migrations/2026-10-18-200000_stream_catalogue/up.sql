@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE stream_catalogue (
+    provider stream_source NOT NULL,
+    locale VARCHAR NOT NULL,
+    id VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    box_art_url VARCHAR,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider, locale, id)
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE stream_title DROP COLUMN timezone;
+
+DROP TABLE preset_counter;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE preset_counter (
+    associated_title INT NOT NULL REFERENCES stream_title (id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    value INT NOT NULL DEFAULT 0,
+    PRIMARY KEY (associated_title, name)
+);
+
+-- `{date}` and `{weekday}` are rendered in this IANA timezone, UTC when unset
+ALTER TABLE stream_title ADD COLUMN timezone VARCHAR;
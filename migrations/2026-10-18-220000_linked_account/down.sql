@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX scheduled_preset_associated_profile_idx;
+DROP INDEX preset_application_associated_profile_idx;
+DROP INDEX stream_title_associated_profile_idx;
+
+ALTER TABLE scheduled_preset DROP COLUMN associated_profile;
+ALTER TABLE preset_application DROP COLUMN associated_profile;
+ALTER TABLE stream_title DROP COLUMN associated_profile;
+
+DROP TABLE linked_account;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE linked_account (
+    associated_profile INT NOT NULL,
+    provider stream_source NOT NULL,
+    platform_user_id VARCHAR NOT NULL,
+    linked_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (provider, platform_user_id),
+    UNIQUE (associated_profile, provider)
+);
+
+-- profiles live in the auth service so existing rows can't be backfilled here, linking an account
+-- sets the profile on all of its rows and until then they are owned by their platform account
+ALTER TABLE stream_title ADD COLUMN associated_profile INT;
+ALTER TABLE preset_application ADD COLUMN associated_profile INT;
+ALTER TABLE scheduled_preset ADD COLUMN associated_profile INT;
+
+CREATE INDEX stream_title_associated_profile_idx ON stream_title (associated_profile, provider);
+CREATE INDEX preset_application_associated_profile_idx ON preset_application (associated_profile, provider);
+CREATE INDEX scheduled_preset_associated_profile_idx ON scheduled_preset (associated_profile);
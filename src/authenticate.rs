@@ -1,13 +1,164 @@
+use std::fs;
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
 use rocket::{
     http::Status,
     info,
     request::{FromRequest, Outcome},
+    warn,
 };
+use serde::Deserialize;
 
 use async_trait::async_trait;
 
+use crate::{profile_cache::ProfileCache, service::Profile, GlobalConfig};
+
+/// The raw header value, plus the claims when it is a BeemStream JWT that verified. Platform tokens
+/// and opaque BeemStream tokens carry no claims and are checked by whoever they're sent on to.
 #[derive(Debug)]
-pub struct AccessToken(pub String);
+pub struct AccessToken(pub String, pub Option<Claims>);
+
+impl AccessToken {
+    /// The BeemStream profile behind the token, only asking `auth_url` when there are no claims.
+    pub async fn profile(
+        &self,
+        profile_cache: &ProfileCache,
+        auth_url: &str,
+    ) -> Result<Profile, Status> {
+        match &self.1 {
+            Some(claims) => Ok(Profile {
+                id: claims.profile_id,
+            }),
+            None => profile_cache.get_profile(&self.0, auth_url).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Claims {
+    #[serde(rename = "sub", deserialize_with = "deserialize_profile_id")]
+    pub profile_id: i32,
+    pub exp: u64,
+}
+
+/// BeemStream puts the profile id in `sub`, which JWTs require to be a string.
+fn deserialize_profile_id<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+struct VerifierKey {
+    key_id: Option<String>,
+    key: DecodingKey,
+    algorithms: Vec<Algorithm>,
+}
+
+/// Verifies BeemStream JWTs against the configured HMAC secret and JWKS file, held in managed
+/// state. With neither configured every token goes to `auth_url` as before. Tokens must be issued
+/// for `jwt_audience`, so ones meant for another service using the same keys are rejected.
+pub struct TokenVerifier {
+    keys: Vec<VerifierKey>,
+    audience: String,
+}
+
+fn jwk_algorithms(parameters: &AlgorithmParameters) -> Vec<Algorithm> {
+    match parameters {
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(ec) => match ec.curve {
+            EllipticCurve::P256 => vec![Algorithm::ES256],
+            EllipticCurve::P384 => vec![Algorithm::ES384],
+            _ => vec![],
+        },
+        AlgorithmParameters::OctetKeyPair(_) => vec![Algorithm::EdDSA],
+        AlgorithmParameters::OctetKey(_) => {
+            vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512]
+        }
+    }
+}
+
+impl TokenVerifier {
+    pub fn from(global_config: &GlobalConfig) -> Self {
+        let mut keys = vec![];
+
+        if let Some(secret) = &global_config.jwt_secret {
+            keys.push(VerifierKey {
+                key_id: None,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+                algorithms: vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512],
+            });
+        }
+
+        if let Some(path) = &global_config.jwks_path {
+            let jwks: JwkSet = fs::read_to_string(path)
+                .ok()
+                .and_then(|jwks| serde_json::from_str(&jwks).ok())
+                .expect("readable JWKS file");
+
+            for jwk in &jwks.keys {
+                match DecodingKey::from_jwk(jwk) {
+                    Ok(key) => keys.push(VerifierKey {
+                        key_id: jwk.common.key_id.clone(),
+                        key,
+                        algorithms: jwk_algorithms(&jwk.algorithm),
+                    }),
+                    Err(e) => warn!("skipping unusable JWKS key: {}", e),
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            warn!("no JWT secret or JWKS configured, every token is checked against auth_url");
+        }
+        let audience = match (&global_config.jwt_audience, keys.is_empty()) {
+            (Some(audience), _) => audience.clone(),
+            (None, true) => String::new(),
+            (None, false) => panic!("jwt_audience must be set along with jwt_secret or jwks_path"),
+        };
+
+        Self { keys, audience }
+    }
+
+    /// `Ok(None)` for anything that isn't a JWT, `Err` for a JWT that doesn't verify.
+    pub fn verify(&self, token: &str) -> Result<Option<Claims>, AccessTokenError> {
+        if self.keys.is_empty() {
+            return Ok(None);
+        }
+        let header = match decode_header(token) {
+            Ok(header) => header,
+            Err(_) => return Ok(None),
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "aud"]);
+
+        self.keys
+            .iter()
+            .filter(|k| match (&header.kid, &k.key_id) {
+                (Some(kid), Some(key_id)) => kid == key_id,
+                _ => true,
+            })
+            .filter(|k| k.algorithms.contains(&header.alg))
+            .find_map(|k| decode::<Claims>(token, &k.key, &validation).ok())
+            .map(|data| Some(data.claims))
+            .ok_or(AccessTokenError::Invalid)
+    }
+}
 
 #[derive(Debug)]
 pub enum AccessTokenError {
@@ -15,9 +166,15 @@ pub enum AccessTokenError {
     Invalid,
 }
 
+fn bearer_token(token: &str) -> Option<&str> {
+    token
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
 pub fn is_token_valid(token: &str) -> bool {
-    let request_token: Vec<&str> = token.split(" ").collect();
-    let is_valid = request_token.starts_with(&["Bearer"]);
+    let is_valid = bearer_token(token).is_some();
 
     info!("is token validated {}", is_valid);
 
@@ -30,9 +187,170 @@ impl<'r> FromRequest<'r> for AccessToken {
 
     async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
         let keys: Vec<&str> = request.headers().get("token").collect();
+        let token = match keys.len() {
+            1 if is_token_valid(keys[0]) => keys[0],
+            0 => return Outcome::Failure((Status::Unauthorized, AccessTokenError::Missing)),
+            _ => return Outcome::Failure((Status::Unauthorized, AccessTokenError::Invalid)),
+        };
+
+        let verified = match request.rocket().state::<TokenVerifier>() {
+            Some(verifier) => verifier.verify(bearer_token(token).unwrap_or_default()),
+            None => Ok(None),
+        };
+        match verified {
+            Ok(claims) => Outcome::Success(AccessToken(token.to_string(), claims)),
+            Err(e) => Outcome::Failure((Status::Unauthorized, e)),
+        }
+    }
+}
+
+/// Headers a platform token is sent in next to the BeemStream `token`, `twitch-token` is what the
+/// Twitch follow import has always taken.
+const PLATFORM_TOKEN_HEADERS: [&str; 2] = ["platform-token", "twitch-token"];
+
+/// The header holding the platform token when it's sent next to the BeemStream `token`. Clients
+/// from before profiles send only their platform token, in `token`.
+fn platform_token_header(request: &rocket::Request<'_>) -> Option<&'static str> {
+    PLATFORM_TOKEN_HEADERS
+        .iter()
+        .copied()
+        .find(|header| request.headers().contains(*header))
+}
+
+/// Streaming platform user token, Twitch or YouTube, see `platform_token_header`.
+#[derive(Debug)]
+pub struct PlatformAccessToken(pub String);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for PlatformAccessToken {
+    type Error = AccessTokenError;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let header = platform_token_header(request).unwrap_or("token");
+        let keys: Vec<&str> = request.headers().get(header).collect();
         match keys.len() {
-            1 if is_token_valid(keys[0]) => Outcome::Success(AccessToken(keys[0].to_string())),
+            0 => Outcome::Failure((Status::Unauthorized, AccessTokenError::Missing)),
+            1 if is_token_valid(keys[0]) => {
+                Outcome::Success(PlatformAccessToken(keys[0].to_string()))
+            }
             _ => Outcome::Failure((Status::Unauthorized, AccessTokenError::Invalid)),
         }
     }
 }
+
+/// Whoever is managing their stream: the platform token acting on their channel and, when they
+/// also sent their BeemStream token, the profile that owns their presets. Without one the
+/// profile is whichever one the platform account is linked to, see
+/// `stream_management::get_platform_user`.
+#[derive(Debug)]
+pub struct StreamIdentity {
+    pub profile_id: Option<i32>,
+    pub platform_token: PlatformAccessToken,
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for StreamIdentity {
+    type Error = AccessTokenError;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let platform_token = match request.guard::<PlatformAccessToken>().await {
+            Outcome::Success(platform_token) => platform_token,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+        };
+        if platform_token_header(request).is_none() {
+            return Outcome::Success(StreamIdentity {
+                profile_id: None,
+                platform_token,
+            });
+        }
+
+        let access_token = match request.guard::<AccessToken>().await {
+            Outcome::Success(access_token) => access_token,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
+        };
+        let (profile_cache, global_config) = match (
+            request.rocket().state::<ProfileCache>(),
+            request.rocket().state::<GlobalConfig>(),
+        ) {
+            (Some(profile_cache), Some(global_config)) => (profile_cache, global_config),
+            _ => return Outcome::Failure((Status::InternalServerError, AccessTokenError::Invalid)),
+        };
+
+        match access_token
+            .profile(profile_cache, &global_config.auth_url)
+            .await
+        {
+            Ok(profile) => Outcome::Success(StreamIdentity {
+                profile_id: Some(profile.id),
+                platform_token,
+            }),
+            Err(status) => Outcome::Failure((status, AccessTokenError::Invalid)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn verifier() -> TokenVerifier {
+        TokenVerifier {
+            keys: vec![VerifierKey {
+                key_id: None,
+                key: DecodingKey::from_secret(b"beemstream-secret"),
+                algorithms: vec![Algorithm::HS256],
+            }],
+            audience: "user-config-service".to_owned(),
+        }
+    }
+
+    fn token(secret: &[u8], audience: &str, expires_in: i64) -> String {
+        signed(
+            secret,
+            serde_json::json!({
+                "sub": "42",
+                "aud": audience,
+                "exp": chrono::Utc::now().timestamp() + expires_in,
+            }),
+        )
+    }
+
+    fn signed(secret: &[u8], claims: serde_json::Value) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn verifies_signature_expiry_and_audience() {
+        let verifier = verifier();
+
+        let claims = verifier
+            .verify(&token(b"beemstream-secret", "user-config-service", 300))
+            .unwrap()
+            .unwrap();
+        assert_eq!(claims.profile_id, 42);
+
+        for rejected in [
+            token(b"another-secret", "user-config-service", 300),
+            token(b"beemstream-secret", "another-service", 300),
+            token(b"beemstream-secret", "user-config-service", -300),
+            signed(
+                b"beemstream-secret",
+                serde_json::json!({
+                    "sub": "42",
+                    "exp": chrono::Utc::now().timestamp() + 300,
+                }),
+            ),
+        ] {
+            assert!(verifier.verify(&rejected).is_err());
+        }
+        assert!(verifier.verify("opaque-platform-token").unwrap().is_none());
+    }
+}
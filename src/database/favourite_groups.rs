@@ -0,0 +1,210 @@
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    database::favourite_streams::SavedFavouriteStreamsModel,
+    schema::{favourite_group, favourite_group_member, favourite_streams},
+    DbConn,
+};
+
+#[derive(Debug, Insertable)]
+#[table_name = "favourite_group"]
+pub struct FavouriteGroupModel {
+    pub associated_user: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct SavedFavouriteGroupModel {
+    pub id: i32,
+    #[serde(skip_serializing)]
+    pub associated_user: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "favourite_group_member"]
+pub struct FavouriteGroupMemberModel {
+    pub associated_group: i32,
+    pub associated_favourite: i32,
+}
+
+/// Returns `None` when the user already has a group with that name.
+pub async fn insert_favourite_group(
+    db_conn: &DbConn,
+    group: FavouriteGroupModel,
+) -> Result<Option<SavedFavouriteGroupModel>, Status> {
+    db_conn
+        .run(|c| {
+            diesel::insert_into(favourite_group::table)
+                .values(group)
+                .on_conflict((favourite_group::associated_user, favourite_group::name))
+                .do_nothing()
+                .get_result::<SavedFavouriteGroupModel>(c)
+                .optional()
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn find_all_favourite_groups(
+    db_conn: &DbConn,
+    associated_user: i32,
+) -> Result<Vec<SavedFavouriteGroupModel>, Status> {
+    db_conn
+        .run(move |c| {
+            favourite_group::table
+                .filter(favourite_group::associated_user.eq(associated_user))
+                .order(favourite_group::name)
+                .get_results::<SavedFavouriteGroupModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn find_favourite_group(
+    db_conn: &DbConn,
+    associated_user: i32,
+    id: i32,
+) -> Result<SavedFavouriteGroupModel, Status> {
+    db_conn
+        .run(move |c| {
+            favourite_group::table
+                .filter(favourite_group::associated_user.eq(associated_user))
+                .filter(favourite_group::id.eq(id))
+                .get_result::<SavedFavouriteGroupModel>(c)
+                .map_err(|_| Status::NotFound)
+        })
+        .await
+}
+
+pub async fn rename_favourite_group(
+    db_conn: &DbConn,
+    associated_user: i32,
+    id: i32,
+    name: String,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::update(
+                favourite_group::table
+                    .filter(favourite_group::associated_user.eq(associated_user))
+                    .filter(favourite_group::id.eq(id)),
+            )
+            .set(favourite_group::name.eq(name))
+            .execute(c)
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::UniqueViolation,
+                    _,
+                ) => Status::Conflict,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+pub async fn delete_favourite_group(
+    db_conn: &DbConn,
+    associated_user: i32,
+    id: i32,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::delete(
+                favourite_group::table
+                    .filter(favourite_group::associated_user.eq(associated_user))
+                    .filter(favourite_group::id.eq(id)),
+            )
+            .execute(c)
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Adds a favourite to a group, both of which must belong to `associated_user`.
+pub async fn insert_favourite_group_member(
+    db_conn: &DbConn,
+    associated_user: i32,
+    member: FavouriteGroupMemberModel,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let owned_group = favourite_group::table
+                    .filter(favourite_group::associated_user.eq(associated_user))
+                    .filter(favourite_group::id.eq(member.associated_group))
+                    .count()
+                    .get_result::<i64>(c)?;
+                let owned_favourite = favourite_streams::table
+                    .filter(favourite_streams::associated_user.eq(associated_user))
+                    .filter(favourite_streams::id.eq(member.associated_favourite))
+                    .count()
+                    .get_result::<i64>(c)?;
+
+                if owned_group == 0 || owned_favourite == 0 {
+                    return Err(diesel::result::Error::NotFound);
+                }
+
+                diesel::insert_into(favourite_group_member::table)
+                    .values(member)
+                    .on_conflict_do_nothing()
+                    .execute(c)
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Status::NotFound,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+pub async fn delete_favourite_group_member(
+    db_conn: &DbConn,
+    associated_user: i32,
+    member: FavouriteGroupMemberModel,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            let owned_groups = favourite_group::table
+                .select(favourite_group::id)
+                .filter(favourite_group::associated_user.eq(associated_user));
+
+            diesel::delete(
+                favourite_group_member::table
+                    .filter(favourite_group_member::associated_group.eq(member.associated_group))
+                    .filter(
+                        favourite_group_member::associated_favourite
+                            .eq(member.associated_favourite),
+                    )
+                    .filter(favourite_group_member::associated_group.eq_any(owned_groups)),
+            )
+            .execute(c)
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn find_favourited_streamers_in_group(
+    db_conn: &DbConn,
+    associated_user: i32,
+    group: i32,
+) -> Result<Vec<SavedFavouriteStreamsModel>, Status> {
+    db_conn
+        .run(move |c| {
+            favourite_streams::table
+                .inner_join(favourite_group_member::table)
+                .filter(favourite_streams::associated_user.eq(associated_user))
+                .filter(favourite_group_member::associated_group.eq(group))
+                .select(favourite_streams::all_columns)
+                .order((
+                    favourite_streams::pinned.desc(),
+                    favourite_streams::position,
+                    favourite_streams::id,
+                ))
+                .get_results::<SavedFavouriteStreamsModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
@@ -1,22 +1,63 @@
+use std::{io::Write, str::FromStr};
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, IsNull, Output, ToSql},
+};
 use rocket::http::Status;
 use rocket_sync_db_pools::diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{favourite_streams::FavouriteStreamsRequest, schema::favourite_streams, DbConn};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "stream_source")]
+pub struct StreamSourceType;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, AsExpression, FromSqlRow,
+)]
+#[sql_type = "StreamSourceType"]
 pub enum StreamSource {
     Twitch,
     Youtube,
+    BeemStream,
 }
 
 impl StreamSource {
-    pub fn from(source: StreamSource) -> String {
-        match source {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
             StreamSource::Twitch => "Twitch",
-            StreamSource::Youtube => "Toutube",
+            StreamSource::Youtube => "Youtube",
+            StreamSource::BeemStream => "BeemStream",
+        }
+    }
+}
+
+impl FromStr for StreamSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "Twitch" => Ok(StreamSource::Twitch),
+            "Youtube" => Ok(StreamSource::Youtube),
+            "BeemStream" => Ok(StreamSource::BeemStream),
+            _ => Err(format!("unknown source {}", source)),
         }
-        .to_owned()
+    }
+}
+
+impl ToSql<StreamSourceType, Pg> for StreamSource {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<StreamSourceType, Pg> for StreamSource {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        Ok(std::str::from_utf8(not_none!(bytes))?.parse()?)
     }
 }
 
@@ -25,7 +66,7 @@ impl StreamSource {
 pub struct FavouriteStreamsModel {
     pub associated_user: i32,
     pub identifier: String,
-    pub source: String,
+    pub source: StreamSource,
 }
 
 #[derive(Debug, Insertable, Queryable, Serialize)]
@@ -34,7 +75,9 @@ pub struct SavedFavouriteStreamsModel {
     pub id: i32,
     pub associated_user: i32,
     pub identifier: String,
-    pub source: String,
+    pub source: StreamSource,
+    pub position: i32,
+    pub pinned: bool,
 }
 
 impl FavouriteStreamsModel {
@@ -42,39 +85,121 @@ impl FavouriteStreamsModel {
         Self {
             associated_user: user,
             identifier: streamer.identifier,
-            source: StreamSource::from(streamer.source),
+            source: streamer.source,
         }
     }
 }
 
+/// Namespaces the advisory lock taken while appending to a user's favourites.
+const FAVOURITE_POSITION_LOCK: i32 = 1;
+
+/// The position after the user's last favourite. Holds a lock on the user's positions until the
+/// transaction ends, so concurrent inserts don't read the same last position.
+fn next_favourite_position(c: &PgConnection, associated_user: i32) -> QueryResult<i32> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1, $2)")
+        .bind::<diesel::sql_types::Integer, _>(FAVOURITE_POSITION_LOCK)
+        .bind::<diesel::sql_types::Integer, _>(associated_user)
+        .execute(c)?;
+
+    let last_position = favourite_streams::table
+        .select(diesel::dsl::max(favourite_streams::position))
+        .filter(favourite_streams::associated_user.eq(associated_user))
+        .get_result::<Option<i32>>(c)?;
+    Ok(last_position.map_or(0, |p| p + 1))
+}
+
+/// New favourites are appended after the user's current last position.
 pub async fn insert_favourite_streamer(
     db_conn: &DbConn,
     streamer: FavouriteStreamsModel,
 ) -> Result<usize, Status> {
     db_conn
         .run(|c| {
-            diesel::insert_into(favourite_streams::table)
-                .values(streamer)
-                .execute(c)
-                .map_err(|_| Status::NotFound)
+            c.transaction(|| {
+                let position = next_favourite_position(c, streamer.associated_user)?;
+
+                diesel::insert_into(favourite_streams::table)
+                    .values((streamer, favourite_streams::position.eq(position)))
+                    .on_conflict((
+                        favourite_streams::associated_user,
+                        favourite_streams::identifier,
+                        favourite_streams::source,
+                    ))
+                    .do_nothing()
+                    .execute(c)
+            })
+            .map_err(|_: diesel::result::Error| Status::InternalServerError)
         })
         .await
 }
 
-pub async fn find_favourite_streamer(
+/// Appends every new favourite after the user's last position, returning how many were inserted.
+pub async fn insert_favourite_streamers(
     db_conn: &DbConn,
     associated_user: i32,
-    streamer: String,
-    source: String,
+    streamers: Vec<FavouriteStreamsModel>,
 ) -> Result<usize, Status> {
     db_conn
         .run(move |c| {
-            favourite_streams::table
-                .filter(favourite_streams::associated_user.eq(associated_user))
-                .filter(favourite_streams::identifier.eq(streamer))
-                .filter(favourite_streams::source.eq(source))
-                .execute(c)
-                .map_err(|_| Status::InternalServerError)
+            c.transaction(|| {
+                let first_position = next_favourite_position(c, associated_user)?;
+
+                let values = streamers
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, streamer)| {
+                        (
+                            streamer,
+                            favourite_streams::position.eq(first_position + i as i32),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                diesel::insert_into(favourite_streams::table)
+                    .values(values)
+                    .on_conflict((
+                        favourite_streams::associated_user,
+                        favourite_streams::identifier,
+                        favourite_streams::source,
+                    ))
+                    .do_nothing()
+                    .execute(c)
+            })
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Inserts each favourite in order within one transaction, reporting which ones were new.
+pub async fn import_favourite_streamers(
+    db_conn: &DbConn,
+    associated_user: i32,
+    streamers: Vec<FavouriteStreamsModel>,
+) -> Result<Vec<bool>, Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let mut next_position = next_favourite_position(c, associated_user)?;
+                let mut inserted = vec![];
+
+                for streamer in streamers {
+                    let count = diesel::insert_into(favourite_streams::table)
+                        .values((streamer, favourite_streams::position.eq(next_position)))
+                        .on_conflict((
+                            favourite_streams::associated_user,
+                            favourite_streams::identifier,
+                            favourite_streams::source,
+                        ))
+                        .do_nothing()
+                        .execute(c)?;
+
+                    next_position += count as i32;
+                    inserted.push(count > 0);
+                }
+
+                Ok(inserted)
+            })
+            .map_err(|_: diesel::result::Error| Status::InternalServerError)
         })
         .await
 }
@@ -87,8 +212,109 @@ pub async fn find_all_favourited_streamers(
         .run(move |c| {
             favourite_streams::table
                 .filter(favourite_streams::associated_user.eq(associated_user))
+                .order((
+                    favourite_streams::pinned.desc(),
+                    favourite_streams::position,
+                    favourite_streams::id,
+                ))
                 .get_results::<SavedFavouriteStreamsModel>(c)
                 .map_err(|_| Status::InternalServerError)
         })
         .await
 }
+
+pub async fn delete_favourite_streamer(
+    db_conn: &DbConn,
+    associated_user: i32,
+    streamer: String,
+    source: StreamSource,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::delete(
+                favourite_streams::table
+                    .filter(favourite_streams::associated_user.eq(associated_user))
+                    .filter(favourite_streams::identifier.eq(streamer))
+                    .filter(favourite_streams::source.eq(source)),
+            )
+            .execute(c)
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn delete_favourite_streamer_by_id(
+    db_conn: &DbConn,
+    associated_user: i32,
+    id: i32,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::delete(
+                favourite_streams::table
+                    .filter(favourite_streams::associated_user.eq(associated_user))
+                    .filter(favourite_streams::id.eq(id)),
+            )
+            .execute(c)
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Rewrites every position for the user, `ids` must be exactly the user's favourites in order.
+pub async fn update_favourite_streamers_order(
+    db_conn: &DbConn,
+    associated_user: i32,
+    ids: Vec<i32>,
+) -> Result<(), Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let mut owned_ids = favourite_streams::table
+                    .select(favourite_streams::id)
+                    .filter(favourite_streams::associated_user.eq(associated_user))
+                    .for_update()
+                    .get_results::<i32>(c)?;
+                let mut requested_ids = ids.clone();
+
+                owned_ids.sort_unstable();
+                requested_ids.sort_unstable();
+                if owned_ids != requested_ids {
+                    return Err(diesel::result::Error::NotFound);
+                }
+
+                for (position, id) in ids.into_iter().enumerate() {
+                    diesel::update(favourite_streams::table.find(id))
+                        .set(favourite_streams::position.eq(position as i32))
+                        .execute(c)?;
+                }
+
+                Ok(())
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Status::UnprocessableEntity,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+pub async fn update_favourite_streamer_pinned(
+    db_conn: &DbConn,
+    associated_user: i32,
+    id: i32,
+    pinned: bool,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::update(
+                favourite_streams::table
+                    .filter(favourite_streams::associated_user.eq(associated_user))
+                    .filter(favourite_streams::id.eq(id)),
+            )
+            .set(favourite_streams::pinned.eq(pinned))
+            .execute(c)
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
@@ -0,0 +1,88 @@
+use std::io::Write;
+
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::Pg,
+    serialize::{self, IsNull, Output, ToSql},
+};
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{schema::favourite_streams_settings, DbConn};
+
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "favourite_visibility")]
+pub struct FavouriteVisibilityType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, AsExpression, FromSqlRow)]
+#[sql_type = "FavouriteVisibilityType"]
+pub enum FavouriteVisibility {
+    Private,
+    Public,
+    Unlisted,
+}
+
+impl ToSql<FavouriteVisibilityType, Pg> for FavouriteVisibility {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            FavouriteVisibility::Private => out.write_all(b"Private")?,
+            FavouriteVisibility::Public => out.write_all(b"Public")?,
+            FavouriteVisibility::Unlisted => out.write_all(b"Unlisted")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<FavouriteVisibilityType, Pg> for FavouriteVisibility {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"Private" => Ok(FavouriteVisibility::Private),
+            b"Public" => Ok(FavouriteVisibility::Public),
+            b"Unlisted" => Ok(FavouriteVisibility::Unlisted),
+            _ => Err("unrecognized favourite visibility".into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "favourite_streams_settings"]
+pub struct FavouriteStreamsSettingsModel {
+    pub associated_user: i32,
+    pub visibility: FavouriteVisibility,
+}
+
+/// Users without a settings row have never shared their list, so they default to private.
+pub async fn find_favourite_visibility(
+    db_conn: &DbConn,
+    associated_user: i32,
+) -> Result<FavouriteVisibility, Status> {
+    db_conn
+        .run(move |c| {
+            favourite_streams_settings::table
+                .select(favourite_streams_settings::visibility)
+                .filter(favourite_streams_settings::associated_user.eq(associated_user))
+                .get_result::<FavouriteVisibility>(c)
+                .optional()
+                .map(|v| v.unwrap_or(FavouriteVisibility::Private))
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn upsert_favourite_visibility(
+    db_conn: &DbConn,
+    settings: FavouriteStreamsSettingsModel,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::insert_into(favourite_streams_settings::table)
+                .values(&settings)
+                .on_conflict(favourite_streams_settings::associated_user)
+                .do_update()
+                .set(favourite_streams_settings::visibility.eq(settings.visibility))
+                .execute(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
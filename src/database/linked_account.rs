@@ -0,0 +1,238 @@
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::{
+    prelude::*,
+    result::{DatabaseErrorKind, Error},
+};
+
+use crate::{
+    database::favourite_streams::StreamSource,
+    schema::{
+        linked_account, preset_application, scheduled_preset, stream_title, twitch_credential,
+    },
+    DbConn,
+};
+
+#[derive(Debug, Insertable)]
+#[table_name = "linked_account"]
+pub struct LinkedAccountModel {
+    pub associated_profile: i32,
+    pub provider: StreamSource,
+    pub platform_user_id: String,
+}
+
+#[derive(Debug, Queryable, PartialEq, Clone)]
+pub struct SavedLinkedAccountModel {
+    pub associated_profile: i32,
+    pub provider: StreamSource,
+    pub platform_user_id: String,
+    pub linked_at: DateTime<Utc>,
+}
+
+/// Links a platform account to a profile, replacing the profile's previous account on that
+/// platform, and hands the profile the presets, history and schedules saved for the account before
+/// it was linked. Returns `Conflict` when the account already belongs to another profile, including
+/// when another profile links it at the same time.
+pub async fn link_account(
+    db_conn: &DbConn,
+    account: LinkedAccountModel,
+) -> Result<SavedLinkedAccountModel, Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let existing = linked_account::table
+                    .find((account.provider, &account.platform_user_id))
+                    .for_update()
+                    .get_result::<SavedLinkedAccountModel>(c)
+                    .optional()?;
+
+                match existing {
+                    Some(existing) if existing.associated_profile == account.associated_profile => {
+                        return Ok(existing);
+                    }
+                    Some(_) => return Err(Error::RollbackTransaction),
+                    None => {}
+                }
+
+                diesel::delete(
+                    linked_account::table
+                        .filter(linked_account::associated_profile.eq(account.associated_profile))
+                        .filter(linked_account::provider.eq(account.provider)),
+                )
+                .execute(c)?;
+                let linked = diesel::insert_into(linked_account::table)
+                    .values(&account)
+                    .get_result::<SavedLinkedAccountModel>(c)?;
+
+                diesel::update(
+                    stream_title::table
+                        .filter(stream_title::associated_user.eq(&account.platform_user_id))
+                        .filter(stream_title::provider.eq(account.provider))
+                        .filter(stream_title::associated_profile.is_null()),
+                )
+                .set(stream_title::associated_profile.eq(account.associated_profile))
+                .execute(c)?;
+                diesel::update(
+                    preset_application::table
+                        .filter(preset_application::associated_user.eq(&account.platform_user_id))
+                        .filter(preset_application::provider.eq(account.provider))
+                        .filter(preset_application::associated_profile.is_null()),
+                )
+                .set(preset_application::associated_profile.eq(account.associated_profile))
+                .execute(c)?;
+                // only Twitch presets can be scheduled
+                if account.provider == StreamSource::Twitch {
+                    diesel::update(
+                        scheduled_preset::table
+                            .filter(scheduled_preset::associated_user.eq(&account.platform_user_id))
+                            .filter(scheduled_preset::associated_profile.is_null()),
+                    )
+                    .set(scheduled_preset::associated_profile.eq(account.associated_profile))
+                    .execute(c)?;
+                }
+
+                Ok(linked)
+            })
+            .map_err(|e| match e {
+                Error::RollbackTransaction
+                | Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Status::Conflict,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+/// Unlinks the profile's account on a platform, along with the credentials kept to act on it.
+/// Presets, history and schedules stay with the profile.
+pub async fn delete_linked_account(
+    db_conn: &DbConn,
+    associated_profile: i32,
+    provider: StreamSource,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let unlinked = diesel::delete(
+                    linked_account::table
+                        .filter(linked_account::associated_profile.eq(associated_profile))
+                        .filter(linked_account::provider.eq(provider)),
+                )
+                .get_results::<SavedLinkedAccountModel>(c)?;
+
+                if provider == StreamSource::Twitch {
+                    for account in &unlinked {
+                        diesel::delete(twitch_credential::table.find(&account.platform_user_id))
+                            .execute(c)?;
+                    }
+                }
+
+                Ok(unlinked.len())
+            })
+            .map_err(|_: Error| Status::InternalServerError)
+        })
+        .await
+}
+
+/// The profile a platform account is linked to, for work done without a BeemStream token.
+pub async fn find_linked_profile(
+    db_conn: &DbConn,
+    provider: StreamSource,
+    platform_user_id: String,
+) -> Result<Option<i32>, Status> {
+    db_conn
+        .run(move |c| {
+            linked_account::table
+                .find((provider, platform_user_id))
+                .select(linked_account::associated_profile)
+                .get_result::<i32>(c)
+                .optional()
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn find_linked_accounts(
+    db_conn: &DbConn,
+    associated_profile: i32,
+) -> Result<Vec<SavedLinkedAccountModel>, Status> {
+    db_conn
+        .run(move |c| {
+            linked_account::table
+                .filter(linked_account::associated_profile.eq(associated_profile))
+                .order(linked_account::linked_at)
+                .get_results::<SavedLinkedAccountModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            stream_management::{find_stream_title, SavedTitleModel},
+            test_db,
+        },
+        stream_management::StreamUser,
+    };
+
+    fn account(associated_profile: i32) -> LinkedAccountModel {
+        LinkedAccountModel {
+            associated_profile,
+            provider: StreamSource::Twitch,
+            platform_user_id: "twitch-user".to_owned(),
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn link_account_adopts_presets_and_rejects_other_profiles() {
+        let db_conn = test_db().await;
+        // saved before presets were owned by a profile
+        let legacy = db_conn
+            .run(|c| {
+                diesel::insert_into(stream_title::table)
+                    .values((
+                        stream_title::associated_user.eq("twitch-user"),
+                        stream_title::title.eq("Legacy preset"),
+                        stream_title::provider.eq(StreamSource::Twitch),
+                    ))
+                    .get_result::<SavedTitleModel>(c)
+            })
+            .await
+            .unwrap();
+
+        let linked = link_account(&db_conn, account(1)).await.unwrap();
+        assert_eq!(link_account(&db_conn, account(1)).await, Ok(linked));
+
+        assert_eq!(
+            link_account(&db_conn, account(2)).await,
+            Err(Status::Conflict)
+        );
+        assert_eq!(
+            find_linked_profile(&db_conn, StreamSource::Twitch, "twitch-user".to_owned())
+                .await
+                .unwrap(),
+            Some(1)
+        );
+        let adopted = find_stream_title(
+            &db_conn,
+            legacy.id,
+            StreamUser {
+                profile_id: Some(1),
+                user_id: "another-account".to_owned(),
+            },
+            StreamSource::Twitch,
+        )
+        .await
+        .unwrap();
+        assert_eq!(adopted.associated_profile, Some(1));
+
+        assert_eq!(
+            delete_linked_account(&db_conn, 1, StreamSource::Twitch).await,
+            Ok(1)
+        );
+        assert!(link_account(&db_conn, account(2)).await.is_ok());
+    }
+}
@@ -1,2 +1,61 @@
+pub mod favourite_groups;
 pub mod favourite_streams;
+pub mod favourite_streams_settings;
+pub mod linked_account;
+pub mod preset_application;
+pub mod preset_counter;
+pub mod scheduled_preset;
+pub mod stream_catalogue;
 pub mod stream_management;
+pub mod twitch_credential;
+
+use rocket::{error, Ignite, Orbit, Rocket};
+
+use crate::DbConn;
+
+/// A single connection pool of its own for a background worker, so the worker can take a
+/// connection per run without holding on to one of the request pool's.
+pub async fn worker_database(rocket: &Rocket<Orbit>) -> Option<Rocket<Ignite>> {
+    let figment = rocket
+        .figment()
+        .clone()
+        .merge(("databases.pg_conn.pool_size", 1));
+
+    match rocket::custom(figment)
+        .attach(DbConn::fairing())
+        .ignite()
+        .await
+    {
+        Ok(database) => Some(database),
+        Err(e) => {
+            error!("no database pool for a background worker: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+use rocket_sync_db_pools::diesel::Connection;
+
+/// Connects to the migrated database at `DATABASE_URL`, every change is rolled back on drop.
+/// Tests using it are `#[ignore]`d, run them with `cargo test -- --ignored`.
+#[cfg(test)]
+pub(crate) async fn test_db() -> DbConn {
+    let url = std::env::var("DATABASE_URL").expect("DATABASE_URL for the database tests");
+    let figment = rocket::Config::figment()
+        .merge(("databases.pg_conn.url", url))
+        .merge(("databases.pg_conn.pool_size", 1));
+    let rocket = rocket::custom(figment)
+        .attach(DbConn::fairing())
+        .ignite()
+        .await
+        .expect("database pool");
+
+    let db_conn = DbConn::get_one(&rocket).await.expect("database connection");
+    db_conn
+        .run(|c| c.begin_test_transaction())
+        .await
+        .expect("test transaction");
+
+    db_conn
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::{pg::Pg, prelude::*, sql_types::Bool};
+use serde_json::Value;
+
+use crate::{
+    database::favourite_streams::StreamSource, schema::preset_application,
+    stream_management::StreamUser, DbConn,
+};
+
+/// Only the most recent applications are kept visible in the history.
+pub const HISTORY_LIMIT: i64 = 50;
+
+#[derive(Debug, Insertable)]
+#[table_name = "preset_application"]
+pub struct PresetApplicationModel {
+    pub associated_user: String,
+    pub associated_title: Option<i32>,
+    pub reverted_application: Option<i32>,
+    pub previous_state: Value,
+    pub applied_state: Value,
+    pub applied: bool,
+    pub platform_status: Option<i32>,
+    pub provider: StreamSource,
+    pub associated_profile: Option<i32>,
+}
+
+#[derive(Debug, Identifiable, Queryable, PartialEq, Clone)]
+#[table_name = "preset_application"]
+pub struct SavedPresetApplicationModel {
+    pub id: i32,
+    pub associated_user: String,
+    pub associated_title: Option<i32>,
+    pub reverted_application: Option<i32>,
+    pub applied_at: DateTime<Utc>,
+    pub previous_state: Value,
+    pub applied_state: Value,
+    pub applied: bool,
+    pub platform_status: Option<i32>,
+    pub provider: StreamSource,
+    pub associated_profile: Option<i32>,
+}
+
+type OwnedApplications = Box<dyn BoxableExpression<preset_application::table, Pg, SqlType = Bool>>;
+
+/// History is kept for the profile, or for the platform account until `link_account` hands it over.
+fn owned_by(user: StreamUser) -> OwnedApplications {
+    match user.profile_id {
+        Some(profile_id) => Box::new(preset_application::associated_profile.eq(profile_id)),
+        None => Box::new(
+            preset_application::associated_profile
+                .is_null()
+                .and(preset_application::associated_user.eq(user.user_id)),
+        ),
+    }
+}
+
+pub async fn insert_preset_application(
+    db_conn: &DbConn,
+    application: PresetApplicationModel,
+) -> Result<SavedPresetApplicationModel, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::insert_into(preset_application::table)
+                .values(application)
+                .get_result::<SavedPresetApplicationModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Newest first, capped at `HISTORY_LIMIT`.
+pub async fn find_preset_applications(
+    db_conn: &DbConn,
+    user: StreamUser,
+    provider: StreamSource,
+) -> Result<Vec<SavedPresetApplicationModel>, Status> {
+    db_conn
+        .run(move |c| {
+            preset_application::table
+                .filter(owned_by(user))
+                .filter(preset_application::provider.eq(provider))
+                .order((
+                    preset_application::applied_at.desc(),
+                    preset_application::id.desc(),
+                ))
+                .limit(HISTORY_LIMIT)
+                .get_results::<SavedPresetApplicationModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Applications made by someone else are reported as `NotFound` so ids can't be probed.
+pub async fn find_preset_application(
+    db_conn: &DbConn,
+    id: i32,
+    user: StreamUser,
+    provider: StreamSource,
+) -> Result<SavedPresetApplicationModel, Status> {
+    db_conn
+        .run(move |c| {
+            preset_application::table
+                .filter(preset_application::id.eq(id))
+                .filter(owned_by(user))
+                .filter(preset_application::provider.eq(provider))
+                .get_result::<SavedPresetApplicationModel>(c)
+                .map_err(|_| Status::NotFound)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+
+    fn user(profile_id: i32, user_id: &str) -> StreamUser {
+        StreamUser {
+            profile_id: Some(profile_id),
+            user_id: user_id.to_owned(),
+        }
+    }
+
+    fn application(user: StreamUser) -> PresetApplicationModel {
+        PresetApplicationModel {
+            associated_user: user.user_id,
+            associated_title: None,
+            reverted_application: None,
+            previous_state: serde_json::json!({ "title": "Before" }),
+            applied_state: serde_json::json!({ "title": "After" }),
+            applied: true,
+            platform_status: None,
+            provider: StreamSource::Twitch,
+            associated_profile: user.profile_id,
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn preset_application_history_is_scoped_to_owner() {
+        let db_conn = test_db().await;
+        let saved = insert_preset_application(&db_conn, application(user(1, "owner")))
+            .await
+            .unwrap();
+
+        let foreign =
+            find_preset_application(&db_conn, saved.id, user(2, "owner"), StreamSource::Twitch)
+                .await;
+        let owned =
+            find_preset_application(&db_conn, saved.id, user(1, "other"), StreamSource::Twitch)
+                .await;
+
+        assert_eq!(foreign, Err(Status::NotFound));
+        assert_eq!(owned, Ok(saved));
+        assert!(
+            find_preset_applications(&db_conn, user(2, "intruder"), StreamSource::Twitch)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
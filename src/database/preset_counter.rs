@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::{pg::upsert::excluded, prelude::*, sql_types::Integer};
+
+use crate::{schema::preset_counter, title_template::MAX_COUNTER_VALUE, DbConn};
+
+sql_function!(fn least(a: Integer, b: Integer) -> Integer);
+
+#[derive(Debug, Insertable, Queryable, PartialEq, Clone)]
+#[table_name = "preset_counter"]
+pub struct PresetCounterModel {
+    pub associated_title: i32,
+    pub name: String,
+    pub value: i32,
+}
+
+pub async fn find_preset_counters(
+    db_conn: &DbConn,
+    associated_title: i32,
+) -> Result<HashMap<String, i32>, Status> {
+    db_conn
+        .run(move |c| {
+            preset_counter::table
+                .filter(preset_counter::associated_title.eq(associated_title))
+                .get_results::<PresetCounterModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+        .map(|counters| counters.into_iter().map(|c| (c.name, c.value)).collect())
+}
+
+/// Sets counters to the given values, counters that aren't mentioned keep counting. Runs on the
+/// caller's connection so counters are saved in the same transaction as their preset.
+pub fn upsert_preset_counters(
+    c: &PgConnection,
+    associated_title: i32,
+    counters: HashMap<String, i32>,
+) -> QueryResult<usize> {
+    let counters: Vec<PresetCounterModel> = counters
+        .into_iter()
+        .map(|(name, value)| PresetCounterModel {
+            associated_title,
+            name,
+            value,
+        })
+        .collect();
+
+    diesel::insert_into(preset_counter::table)
+        .values(counters)
+        .on_conflict((preset_counter::associated_title, preset_counter::name))
+        .do_update()
+        .set(preset_counter::value.eq(excluded(preset_counter::value)))
+        .execute(c)
+}
+
+/// Bumps each named counter by one up to `MAX_COUNTER_VALUE`, counters that don't exist yet start at 1.
+pub async fn increment_preset_counters(
+    db_conn: &DbConn,
+    associated_title: i32,
+    names: Vec<String>,
+) -> Result<(), Status> {
+    let counters: Vec<PresetCounterModel> = names
+        .into_iter()
+        .map(|name| PresetCounterModel {
+            associated_title,
+            name,
+            value: 1,
+        })
+        .collect();
+
+    db_conn
+        .run(move |c| {
+            diesel::insert_into(preset_counter::table)
+                .values(counters)
+                .on_conflict((preset_counter::associated_title, preset_counter::name))
+                .do_update()
+                .set(preset_counter::value.eq(least(preset_counter::value + 1, MAX_COUNTER_VALUE)))
+                .execute(c)
+                .map(|_| ())
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            favourite_streams::StreamSource,
+            stream_management::{insert_stream_preset, update_stream_preset, StreamTitleModel},
+            test_db,
+        },
+        stream_management::{StreamManagementRequest, StreamTitle, StreamUser},
+    };
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn increment_preset_counters_counts_from_the_saved_value() {
+        let db_conn = test_db().await;
+        let request = StreamManagementRequest {
+            title: StreamTitle {
+                title: "Day {counter}, {counter:deaths} deaths".to_owned(),
+            },
+            tags: vec![],
+            game: None,
+            broadcaster_language: None,
+            description: None,
+            counters: Default::default(),
+            timezone: None,
+        };
+        let owner = StreamUser {
+            profile_id: Some(1),
+            user_id: "owner".to_owned(),
+        };
+        let title = || StreamTitleModel::from(&request, owner.clone(), StreamSource::Twitch);
+        let preset = insert_stream_preset(
+            &db_conn,
+            title(),
+            vec![],
+            HashMap::from([("counter".to_owned(), 13)]),
+        )
+        .await
+        .unwrap();
+        increment_preset_counters(
+            &db_conn,
+            preset.id,
+            vec!["counter".to_owned(), "deaths".to_owned()],
+        )
+        .await
+        .unwrap();
+
+        let counters = find_preset_counters(&db_conn, preset.id).await.unwrap();
+        assert_eq!(counters.get("counter"), Some(&14));
+        assert_eq!(counters.get("deaths"), Some(&1));
+
+        update_stream_preset(
+            &db_conn,
+            preset.id,
+            title(),
+            vec![],
+            HashMap::from([("counter".to_owned(), MAX_COUNTER_VALUE)]),
+        )
+        .await
+        .unwrap();
+        increment_preset_counters(&db_conn, preset.id, vec!["counter".to_owned()])
+            .await
+            .unwrap();
+
+        let counters = find_preset_counters(&db_conn, preset.id).await.unwrap();
+        assert_eq!(counters.get("counter"), Some(&MAX_COUNTER_VALUE));
+    }
+}
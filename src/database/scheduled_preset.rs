@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::{pg::Pg, prelude::*, sql_types::Bool};
+
+use crate::{schema::scheduled_preset, stream_management::StreamUser, DbConn};
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[table_name = "scheduled_preset"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ScheduledPresetModel {
+    pub associated_user: String,
+    pub associated_title: i32,
+    pub run_at: DateTime<Utc>,
+    pub associated_profile: Option<i32>,
+}
+
+#[derive(Debug, Identifiable, Queryable, PartialEq, Clone)]
+#[table_name = "scheduled_preset"]
+pub struct SavedScheduledPresetModel {
+    pub id: i32,
+    pub associated_user: String,
+    pub associated_title: i32,
+    pub run_at: DateTime<Utc>,
+    pub ran_at: Option<DateTime<Utc>>,
+    pub applied: Option<bool>,
+    pub platform_status: Option<i32>,
+    pub associated_application: Option<i32>,
+    pub associated_profile: Option<i32>,
+}
+
+/// Outcome of one run of a schedule, `associated_application` is unset when Twitch was never reached.
+#[derive(Debug, AsChangeset)]
+#[table_name = "scheduled_preset"]
+pub struct ScheduledPresetRunModel {
+    pub applied: Option<bool>,
+    pub platform_status: Option<i32>,
+    pub associated_application: Option<i32>,
+}
+
+type OwnedSchedules = Box<dyn BoxableExpression<scheduled_preset::table, Pg, SqlType = Bool>>;
+
+/// Schedules made for an account that isn't linked yet are only the account's.
+fn owned_by(user: StreamUser) -> OwnedSchedules {
+    match user.profile_id {
+        Some(profile_id) => Box::new(scheduled_preset::associated_profile.eq(profile_id)),
+        None => Box::new(
+            scheduled_preset::associated_profile
+                .is_null()
+                .and(scheduled_preset::associated_user.eq(user.user_id)),
+        ),
+    }
+}
+
+pub async fn insert_scheduled_preset(
+    db_conn: &DbConn,
+    schedule: ScheduledPresetModel,
+) -> Result<SavedScheduledPresetModel, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::insert_into(scheduled_preset::table)
+                .values(schedule)
+                .get_result::<SavedScheduledPresetModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn find_scheduled_presets(
+    db_conn: &DbConn,
+    user: StreamUser,
+) -> Result<Vec<SavedScheduledPresetModel>, Status> {
+    db_conn
+        .run(move |c| {
+            scheduled_preset::table
+                .filter(owned_by(user))
+                .order((scheduled_preset::run_at.desc(), scheduled_preset::id.desc()))
+                .get_results::<SavedScheduledPresetModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Moves a schedule that hasn't run yet, returns `NotFound` for schedules the user doesn't own
+/// and `Conflict` once it has run. The schedule now runs as whoever moved it.
+pub async fn update_scheduled_preset(
+    db_conn: &DbConn,
+    id: i32,
+    schedule: ScheduledPresetModel,
+) -> Result<SavedScheduledPresetModel, Status> {
+    let owner = StreamUser {
+        profile_id: schedule.associated_profile,
+        user_id: schedule.associated_user.clone(),
+    };
+
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let owned = scheduled_preset::table
+                    .filter(scheduled_preset::id.eq(id))
+                    .filter(owned_by(owner))
+                    .for_update()
+                    .get_result::<SavedScheduledPresetModel>(c)?;
+
+                if owned.ran_at.is_some() {
+                    return Err(diesel::result::Error::RollbackTransaction);
+                }
+
+                diesel::update(&owned)
+                    .set(&schedule)
+                    .get_result::<SavedScheduledPresetModel>(c)
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Status::NotFound,
+                diesel::result::Error::RollbackTransaction => Status::Conflict,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+pub async fn delete_scheduled_preset(
+    db_conn: &DbConn,
+    id: i32,
+    user: StreamUser,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::delete(
+                scheduled_preset::table
+                    .filter(scheduled_preset::id.eq(id))
+                    .filter(owned_by(user)),
+            )
+            .execute(c)
+            .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Marks every due schedule as run and hands them back, so each one is only ever picked up once.
+pub async fn claim_due_scheduled_presets(
+    db_conn: &DbConn,
+) -> Result<Vec<SavedScheduledPresetModel>, Status> {
+    db_conn
+        .run(|c| {
+            c.transaction(|| {
+                let due_ids = scheduled_preset::table
+                    .select(scheduled_preset::id)
+                    .filter(scheduled_preset::ran_at.is_null())
+                    .filter(scheduled_preset::run_at.le(diesel::dsl::now))
+                    .for_update()
+                    .skip_locked()
+                    .get_results::<i32>(c)?;
+
+                diesel::update(scheduled_preset::table.filter(scheduled_preset::id.eq_any(due_ids)))
+                    .set(scheduled_preset::ran_at.eq(diesel::dsl::now))
+                    .get_results::<SavedScheduledPresetModel>(c)
+            })
+            .map_err(|_: diesel::result::Error| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn update_scheduled_preset_run(
+    db_conn: &DbConn,
+    id: i32,
+    run: ScheduledPresetRunModel,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::update(scheduled_preset::table.find(id))
+                .set(&run)
+                .execute(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::{
+            favourite_streams::StreamSource,
+            stream_management::{insert_stream_preset, StreamTitleModel},
+            test_db,
+        },
+        stream_management::{StreamManagementRequest, StreamTitle},
+    };
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn claim_due_scheduled_presets_claims_once() {
+        let db_conn = test_db().await;
+        let request = StreamManagementRequest {
+            title: StreamTitle {
+                title: "Subathon hour 2".to_owned(),
+            },
+            tags: vec![],
+            game: None,
+            broadcaster_language: None,
+            description: None,
+            counters: Default::default(),
+            timezone: None,
+        };
+        let owner = StreamUser {
+            profile_id: Some(1),
+            user_id: "owner".to_owned(),
+        };
+        let preset = insert_stream_preset(
+            &db_conn,
+            StreamTitleModel::from(&request, owner.clone(), StreamSource::Twitch),
+            request.tags,
+            request.counters,
+        )
+        .await
+        .unwrap();
+
+        let schedule = |run_at| ScheduledPresetModel {
+            associated_user: owner.user_id.clone(),
+            associated_title: preset.id,
+            run_at,
+            associated_profile: owner.profile_id,
+        };
+        let due = insert_scheduled_preset(
+            &db_conn,
+            schedule(Utc::now() - chrono::Duration::minutes(1)),
+        )
+        .await
+        .unwrap();
+        insert_scheduled_preset(&db_conn, schedule(Utc::now() + chrono::Duration::hours(1)))
+            .await
+            .unwrap();
+
+        let claimed = claim_due_scheduled_presets(&db_conn).await.unwrap();
+
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].id, due.id);
+        assert!(claimed[0].ran_at.is_some());
+        assert!(claim_due_scheduled_presets(&db_conn)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::prelude::*;
+
+use crate::{database::favourite_streams::StreamSource, schema::stream_catalogue, DbConn};
+
+#[derive(Debug, Insertable)]
+#[table_name = "stream_catalogue"]
+pub struct StreamCatalogueModel {
+    pub provider: StreamSource,
+    pub locale: String,
+    pub id: String,
+    pub name: String,
+    pub box_art_url: Option<String>,
+}
+
+#[derive(Debug, Queryable, PartialEq, Clone)]
+pub struct SavedStreamCatalogueModel {
+    pub provider: StreamSource,
+    pub locale: String,
+    pub id: String,
+    pub name: String,
+    pub box_art_url: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+pub async fn find_stream_catalogue(
+    db_conn: &DbConn,
+    provider: StreamSource,
+    locale: String,
+) -> Result<Vec<SavedStreamCatalogueModel>, Status> {
+    db_conn
+        .run(move |c| {
+            stream_catalogue::table
+                .filter(stream_catalogue::provider.eq(provider))
+                .filter(stream_catalogue::locale.eq(locale))
+                .order(stream_catalogue::name)
+                .get_results::<SavedStreamCatalogueModel>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Swaps a whole catalogue at once, so readers never see half of an old and a new one.
+pub async fn replace_stream_catalogue(
+    db_conn: &DbConn,
+    provider: StreamSource,
+    locale: String,
+    entries: Vec<StreamCatalogueModel>,
+) -> Result<(), Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                diesel::delete(
+                    stream_catalogue::table
+                        .filter(stream_catalogue::provider.eq(provider))
+                        .filter(stream_catalogue::locale.eq(locale)),
+                )
+                .execute(c)?;
+
+                diesel::insert_into(stream_catalogue::table)
+                    .values(entries)
+                    .on_conflict_do_nothing()
+                    .execute(c)?;
+
+                Ok(())
+            })
+            .map_err(|_: diesel::result::Error| Status::InternalServerError)
+        })
+        .await
+}
+
+/// Every cached catalogue with the time its oldest entry was fetched.
+pub async fn find_stream_catalogue_ages(
+    db_conn: &DbConn,
+) -> Result<Vec<(StreamSource, String, DateTime<Utc>)>, Status> {
+    let fetched = db_conn
+        .run(|c| {
+            stream_catalogue::table
+                .select((
+                    stream_catalogue::provider,
+                    stream_catalogue::locale,
+                    stream_catalogue::fetched_at,
+                ))
+                .distinct()
+                .get_results::<(StreamSource, String, DateTime<Utc>)>(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await?;
+
+    let mut ages: Vec<(StreamSource, String, DateTime<Utc>)> = Vec::new();
+    for (provider, locale, fetched_at) in fetched {
+        match ages
+            .iter_mut()
+            .find(|(p, l, _)| *p == provider && *l == locale)
+        {
+            Some(age) if fetched_at < age.2 => age.2 = fetched_at,
+            Some(_) => {}
+            None => ages.push((provider, locale, fetched_at)),
+        }
+    }
+
+    Ok(ages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+
+    fn entry(id: &str, name: &str) -> StreamCatalogueModel {
+        StreamCatalogueModel {
+            provider: StreamSource::Youtube,
+            locale: "de".to_owned(),
+            id: id.to_owned(),
+            name: name.to_owned(),
+            box_art_url: None,
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn replace_stream_catalogue_drops_stale_entries() {
+        let db_conn = test_db().await;
+        let locale = || "de".to_owned();
+
+        replace_stream_catalogue(
+            &db_conn,
+            StreamSource::Youtube,
+            locale(),
+            vec![entry("20", "Spiele"), entry("10", "Musik")],
+        )
+        .await
+        .unwrap();
+        replace_stream_catalogue(
+            &db_conn,
+            StreamSource::Youtube,
+            locale(),
+            vec![entry("10", "Musik")],
+        )
+        .await
+        .unwrap();
+
+        let catalogue = find_stream_catalogue(&db_conn, StreamSource::Youtube, locale())
+            .await
+            .unwrap();
+        assert_eq!(catalogue.len(), 1);
+        assert_eq!(catalogue[0].name, "Musik");
+        assert!(find_stream_catalogue_ages(&db_conn)
+            .await
+            .unwrap()
+            .iter()
+            .any(|(provider, l, _)| *provider == StreamSource::Youtube && *l == locale()));
+    }
+}
@@ -1,33 +1,72 @@
+use std::collections::HashMap;
+
 use crate::{
+    database::{favourite_streams::StreamSource, preset_counter::upsert_preset_counters},
     schema::{stream_tag, stream_title},
-    stream_management::{StreamTag, StreamTitle},
+    stream_management::{StreamManagementRequest, StreamTag, StreamUser},
     DbConn,
 };
 use rocket::http::Status;
-use rocket_sync_db_pools::diesel::prelude::*;
+use rocket_sync_db_pools::diesel::{pg::Pg, prelude::*, sql_types::Bool};
 use serde::Serialize;
 
-#[derive(Debug, Insertable, Queryable)]
+#[derive(Debug, Insertable, Queryable, AsChangeset)]
 #[table_name = "stream_title"]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct StreamTitleModel {
     pub associated_user: String,
     pub title: String,
+    pub game_id: Option<String>,
+    pub game_name: Option<String>,
+    pub broadcaster_language: Option<String>,
+    pub provider: StreamSource,
+    pub description: Option<String>,
+    pub timezone: Option<String>,
+    pub associated_profile: Option<i32>,
 }
 
 impl StreamTitleModel {
-    pub fn from(stream_title_request: &StreamTitle, user_id: String) -> Self {
+    /// Owned by the user's profile, or only by their platform account while it isn't linked to one.
+    pub fn from(
+        stream_management_request: &StreamManagementRequest,
+        user: StreamUser,
+        provider: StreamSource,
+    ) -> Self {
+        let game = stream_management_request.game.as_ref();
+
         Self {
-            associated_user: user_id,
-            title: stream_title_request.title.clone(),
+            associated_user: user.user_id,
+            title: stream_management_request.title.title.clone(),
+            game_id: game.map(|g| g.id.clone()),
+            game_name: game.map(|g| g.name.clone()),
+            broadcaster_language: stream_management_request.broadcaster_language.clone(),
+            provider,
+            description: stream_management_request.description.clone(),
+            timezone: stream_management_request.timezone.clone(),
+            associated_profile: user.profile_id,
         }
     }
 }
 
+type OwnedTitles = Box<dyn BoxableExpression<stream_title::table, Pg, SqlType = Bool>>;
+
+/// A preset is its profile's, presets without one were saved for an account nobody has linked yet.
+fn owned_by(user: StreamUser) -> OwnedTitles {
+    match user.profile_id {
+        Some(profile_id) => Box::new(stream_title::associated_profile.eq(profile_id)),
+        None => Box::new(
+            stream_title::associated_profile
+                .is_null()
+                .and(stream_title::associated_user.eq(user.user_id)),
+        ),
+    }
+}
+
 #[derive(Debug, Insertable, Queryable)]
 #[table_name = "stream_tag"]
 pub struct StreamTagModel {
     pub associated_title: i32,
-    pub source_id: String,
+    pub source_id: Option<String>,
     pub name: String,
 }
 
@@ -41,66 +80,80 @@ impl StreamTagModel {
     }
 }
 
-pub async fn insert_stream_title(
+/// Saves a preset's title, tags and counters together, so a failing tag never leaves an orphaned title.
+pub async fn insert_stream_preset(
     db_conn: &DbConn,
     stream_title: StreamTitleModel,
+    tags: Vec<StreamTag>,
+    counters: HashMap<String, i32>,
 ) -> Result<SavedTitleModel, Status> {
     db_conn
-        .run(|c| {
-            diesel::insert_into(stream_title::table)
-                .values(stream_title)
-                .get_result::<SavedTitleModel>(c)
-                .map_err(|_| Status::NotFound)
-        })
-        .await
-}
+        .run(move |c| {
+            c.transaction(|| {
+                let saved_title = diesel::insert_into(stream_title::table)
+                    .values(stream_title)
+                    .get_result::<SavedTitleModel>(c)?;
 
-pub async fn insert_stream_tag(
-    db_conn: &DbConn,
-    stream_tag: StreamTagModel,
-) -> Result<usize, Status> {
-    db_conn
-        .run(|c| {
-            diesel::insert_into(stream_tag::table)
-                .values(stream_tag)
-                .execute(c)
-                .map_err(|_| Status::NotFound)
+                let tags: Vec<StreamTagModel> = tags
+                    .iter()
+                    .map(|t| StreamTagModel::from(t, saved_title.id))
+                    .collect();
+                diesel::insert_into(stream_tag::table)
+                    .values(tags)
+                    .execute(c)?;
+                upsert_preset_counters(c, saved_title.id, counters)?;
+
+                Ok(saved_title)
+            })
+            .map_err(|_: diesel::result::Error| Status::InternalServerError)
         })
         .await
 }
 
-pub struct StreamPreset {
-    title: String,
-    tags: Vec<StreamTagModel>,
-}
-
 #[derive(Debug, Identifiable, Insertable, Queryable, PartialEq, Clone)]
 #[table_name = "stream_title"]
 pub struct SavedTitleModel {
     pub id: i32,
     pub associated_user: String,
     pub title: String,
+    pub game_id: Option<String>,
+    pub game_name: Option<String>,
+    pub broadcaster_language: Option<String>,
+    pub provider: StreamSource,
+    pub description: Option<String>,
+    pub timezone: Option<String>,
+    pub associated_profile: Option<i32>,
 }
 
 pub async fn find_stream_titles(
     db_conn: &DbConn,
-    id: String,
+    user: StreamUser,
+    provider: StreamSource,
 ) -> Result<Vec<SavedTitleModel>, Status> {
     db_conn
-        .run(|c| {
+        .run(move |c| {
             stream_title::table
-                .filter(stream_title::associated_user.eq(id))
+                .filter(owned_by(user))
+                .filter(stream_title::provider.eq(provider))
                 .get_results::<SavedTitleModel>(c)
                 .map_err(|_| Status::NotFound)
         })
         .await
 }
 
-pub async fn find_stream_title(db_conn: &DbConn, id: i32) -> Result<SavedTitleModel, Status> {
+/// Presets owned by someone else are reported as `NotFound` so ids can't be probed.
+pub async fn find_stream_title(
+    db_conn: &DbConn,
+    id: i32,
+    user: StreamUser,
+    provider: StreamSource,
+) -> Result<SavedTitleModel, Status> {
     db_conn
         .run(move |c| {
             stream_title::table
                 .filter(stream_title::id.eq(id))
+                .filter(owned_by(user))
+                .filter(stream_title::provider.eq(provider))
                 .get_result::<SavedTitleModel>(c)
                 .map_err(|_| Status::NotFound)
         })
@@ -113,7 +166,7 @@ pub async fn find_stream_title(db_conn: &DbConn, id: i32) -> Result<SavedTitleMo
 pub struct SavedTagModel {
     pub id: i32,
     pub associated_title: i32,
-    pub source_id: String,
+    pub source_id: Option<String>,
     pub name: String,
 }
 
@@ -129,3 +182,210 @@ pub async fn find_stream_tag(
         })
         .await
 }
+
+/// Replaces a preset's title and its whole tag set and sets the given counters, returns
+/// `NotFound` for presets the user doesn't own.
+pub async fn update_stream_preset(
+    db_conn: &DbConn,
+    id: i32,
+    stream_title: StreamTitleModel,
+    tags: Vec<StreamTag>,
+    counters: HashMap<String, i32>,
+) -> Result<(), Status> {
+    let owner = StreamUser {
+        profile_id: stream_title.associated_profile,
+        user_id: stream_title.associated_user.clone(),
+    };
+
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let updated = diesel::update(
+                    stream_title::table
+                        .filter(stream_title::id.eq(id))
+                        .filter(owned_by(owner))
+                        .filter(stream_title::provider.eq(stream_title.provider)),
+                )
+                .set(&stream_title)
+                .execute(c)?;
+
+                if updated == 0 {
+                    return Err(diesel::result::Error::NotFound);
+                }
+
+                diesel::delete(stream_tag::table.filter(stream_tag::associated_title.eq(id)))
+                    .execute(c)?;
+
+                let tags: Vec<StreamTagModel> =
+                    tags.iter().map(|t| StreamTagModel::from(t, id)).collect();
+                diesel::insert_into(stream_tag::table)
+                    .values(tags)
+                    .execute(c)?;
+                upsert_preset_counters(c, id, counters)?;
+
+                Ok(())
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Status::NotFound,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+/// Deletes a preset along with its tags, returns `NotFound` for presets the user doesn't own.
+pub async fn delete_stream_preset(
+    db_conn: &DbConn,
+    id: i32,
+    user: StreamUser,
+    provider: StreamSource,
+) -> Result<(), Status> {
+    db_conn
+        .run(move |c| {
+            c.transaction(|| {
+                let owned = stream_title::table
+                    .filter(stream_title::id.eq(id))
+                    .filter(owned_by(user))
+                    .filter(stream_title::provider.eq(provider))
+                    .for_update()
+                    .get_result::<SavedTitleModel>(c)?;
+
+                diesel::delete(SavedTagModel::belonging_to(&owned)).execute(c)?;
+                diesel::delete(&owned).execute(c)?;
+
+                Ok(())
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => Status::NotFound,
+                _ => Status::InternalServerError,
+            })
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::test_db,
+        stream_management::{StreamTag, StreamTitle},
+    };
+
+    fn preset_request(title: &str) -> StreamManagementRequest {
+        StreamManagementRequest {
+            title: StreamTitle {
+                title: title.to_owned(),
+            },
+            tags: vec![StreamTag {
+                id: None,
+                name: "Speedrun".to_owned(),
+            }],
+            game: None,
+            broadcaster_language: None,
+            description: None,
+            counters: Default::default(),
+            timezone: None,
+        }
+    }
+
+    fn user(profile_id: Option<i32>, user_id: &str) -> StreamUser {
+        StreamUser {
+            profile_id,
+            user_id: user_id.to_owned(),
+        }
+    }
+
+    fn owner() -> StreamUser {
+        user(Some(1), "owner")
+    }
+
+    fn intruder() -> StreamUser {
+        user(Some(2), "intruder")
+    }
+
+    async fn insert_preset(db_conn: &DbConn, user: StreamUser) -> SavedTitleModel {
+        let request = preset_request("Speedrun");
+
+        insert_stream_preset(
+            db_conn,
+            StreamTitleModel::from(&request, user, StreamSource::Twitch),
+            request.tags,
+            request.counters,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn find_stream_title_rejects_other_users() {
+        let db_conn = test_db().await;
+        let preset = insert_preset(&db_conn, owner()).await;
+
+        let foreign =
+            find_stream_title(&db_conn, preset.id, intruder(), StreamSource::Twitch).await;
+        let owned = find_stream_title(&db_conn, preset.id, owner(), StreamSource::Twitch).await;
+
+        assert_eq!(foreign, Err(Status::NotFound));
+        assert_eq!(owned, Ok(preset));
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn unlinked_presets_belong_to_their_platform_account() {
+        let db_conn = test_db().await;
+        let preset = insert_preset(&db_conn, user(None, "legacy")).await;
+
+        assert_eq!(
+            find_stream_titles(&db_conn, user(None, "legacy"), StreamSource::Twitch).await,
+            Ok(vec![preset.clone()])
+        );
+        for viewer in [user(None, "other"), user(Some(1), "legacy")] {
+            assert_eq!(
+                find_stream_title(&db_conn, preset.id, viewer, StreamSource::Twitch).await,
+                Err(Status::NotFound)
+            );
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn update_stream_preset_rejects_other_users() {
+        let db_conn = test_db().await;
+        let preset = insert_preset(&db_conn, owner()).await;
+        let request = preset_request("Hijacked");
+
+        let updated = update_stream_preset(
+            &db_conn,
+            preset.id,
+            StreamTitleModel::from(&request, intruder(), StreamSource::Twitch),
+            vec![],
+            HashMap::new(),
+        )
+        .await;
+
+        assert_eq!(updated, Err(Status::NotFound));
+        let unchanged = find_stream_title(&db_conn, preset.id, owner(), StreamSource::Twitch)
+            .await
+            .unwrap();
+        assert_eq!(unchanged.title, "Speedrun");
+        assert_eq!(find_stream_tag(&db_conn, unchanged).await.unwrap().len(), 1);
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs a migrated Postgres at DATABASE_URL"]
+    async fn delete_stream_preset_rejects_other_users() {
+        let db_conn = test_db().await;
+        let preset = insert_preset(&db_conn, owner()).await;
+
+        let deleted =
+            delete_stream_preset(&db_conn, preset.id, intruder(), StreamSource::Twitch).await;
+
+        assert_eq!(deleted, Err(Status::NotFound));
+        assert!(
+            find_stream_title(&db_conn, preset.id, owner(), StreamSource::Twitch)
+                .await
+                .is_ok()
+        );
+    }
+}
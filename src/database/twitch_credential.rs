@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket_sync_db_pools::diesel::prelude::*;
+
+use crate::{schema::twitch_credential, DbConn};
+
+/// Both tokens are stored encrypted, see `TwitchCredentials`.
+#[derive(Debug, Insertable, Queryable, AsChangeset, PartialEq, Clone)]
+#[table_name = "twitch_credential"]
+pub struct TwitchCredentialModel {
+    pub associated_user: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub async fn upsert_twitch_credential(
+    db_conn: &DbConn,
+    credential: TwitchCredentialModel,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::insert_into(twitch_credential::table)
+                .values(&credential)
+                .on_conflict(twitch_credential::associated_user)
+                .do_update()
+                .set(&credential)
+                .execute(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn find_twitch_credential(
+    db_conn: &DbConn,
+    associated_user: String,
+) -> Result<Option<TwitchCredentialModel>, Status> {
+    db_conn
+        .run(move |c| {
+            twitch_credential::table
+                .find(associated_user)
+                .get_result::<TwitchCredentialModel>(c)
+                .optional()
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
+
+pub async fn delete_twitch_credential(
+    db_conn: &DbConn,
+    associated_user: String,
+) -> Result<usize, Status> {
+    db_conn
+        .run(move |c| {
+            diesel::delete(twitch_credential::table.find(associated_user))
+                .execute(c)
+                .map_err(|_| Status::InternalServerError)
+        })
+        .await
+}
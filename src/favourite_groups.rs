@@ -0,0 +1,156 @@
+use rocket::{delete, get, http::Status, post, put, serde::json::Json, State};
+use serde::Deserialize;
+
+use crate::{
+    authenticate::AccessToken,
+    database::favourite_groups::{
+        delete_favourite_group, delete_favourite_group_member, find_all_favourite_groups,
+        insert_favourite_group, insert_favourite_group_member, rename_favourite_group,
+        FavouriteGroupMemberModel, FavouriteGroupModel, SavedFavouriteGroupModel,
+    },
+    profile_cache::ProfileCache,
+    DbConn, GlobalConfig,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct FavouriteGroupRequest {
+    pub name: String,
+}
+
+impl FavouriteGroupRequest {
+    fn name(self) -> Result<String, Status> {
+        let name = self.name.trim().to_owned();
+        match name.is_empty() {
+            true => Err(Status::UnprocessableEntity),
+            false => Ok(name),
+        }
+    }
+}
+
+#[get("/favourite-groups")]
+pub async fn get_favourite_groups(
+    db_conn: DbConn,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Json<Vec<SavedFavouriteGroupModel>>, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let groups = find_all_favourite_groups(&db_conn, profile.id).await?;
+
+    Ok(Json(groups))
+}
+
+#[post("/favourite-groups", data = "<favourite_group_request>")]
+pub async fn post_favourite_group(
+    db_conn: DbConn,
+    favourite_group_request: Json<FavouriteGroupRequest>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<(Status, Json<SavedFavouriteGroupModel>), Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let group = FavouriteGroupModel {
+        associated_user: profile.id,
+        name: favourite_group_request.into_inner().name()?,
+    };
+
+    match insert_favourite_group(&db_conn, group).await? {
+        Some(saved_group) => Ok((Status::Created, Json(saved_group))),
+        None => Err(Status::Conflict),
+    }
+}
+
+#[put("/favourite-groups/<id>", data = "<favourite_group_request>")]
+pub async fn put_favourite_group(
+    db_conn: DbConn,
+    id: i32,
+    favourite_group_request: Json<FavouriteGroupRequest>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let name = favourite_group_request.into_inner().name()?;
+    let updated = rename_favourite_group(&db_conn, profile.id, id, name).await?;
+
+    match updated > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
+
+#[delete("/favourite-groups/<id>")]
+pub async fn delete_favourite_group_by_id(
+    db_conn: DbConn,
+    id: i32,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let deleted = delete_favourite_group(&db_conn, profile.id, id).await?;
+
+    match deleted > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
+
+#[put("/favourite-groups/<id>/favourite-streams/<favourite_id>")]
+pub async fn put_favourite_group_member(
+    db_conn: DbConn,
+    id: i32,
+    favourite_id: i32,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let member = FavouriteGroupMemberModel {
+        associated_group: id,
+        associated_favourite: favourite_id,
+    };
+    insert_favourite_group_member(&db_conn, profile.id, member).await?;
+
+    Ok(Status::NoContent)
+}
+
+#[delete("/favourite-groups/<id>/favourite-streams/<favourite_id>")]
+pub async fn delete_favourite_group_member_by_id(
+    db_conn: DbConn,
+    id: i32,
+    favourite_id: i32,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let member = FavouriteGroupMemberModel {
+        associated_group: id,
+        associated_favourite: favourite_id,
+    };
+    let deleted = delete_favourite_group_member(&db_conn, profile.id, member).await?;
+
+    match deleted > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
@@ -1,13 +1,23 @@
-use rocket::{debug, get, http::Status, post, serde::json::Json, State};
+use rocket::{debug, delete, get, http::Status, info, post, put, serde::json::Json, State};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    authenticate::AccessToken,
+    authenticate::{AccessToken, StreamIdentity},
+    database::favourite_groups::{find_favourite_group, find_favourited_streamers_in_group},
     database::favourite_streams::{
-        find_all_favourited_streamers, find_favourite_streamer, insert_favourite_streamer,
-        FavouriteStreamsModel, SavedFavouriteStreamsModel, StreamSource,
+        delete_favourite_streamer, delete_favourite_streamer_by_id, find_all_favourited_streamers,
+        insert_favourite_streamer, insert_favourite_streamers, update_favourite_streamer_pinned,
+        update_favourite_streamers_order, FavouriteStreamsModel, SavedFavouriteStreamsModel,
+        StreamSource,
     },
-    service::get_profile,
+    database::favourite_streams_settings::{
+        find_favourite_visibility, upsert_favourite_visibility, FavouriteStreamsSettingsModel,
+        FavouriteVisibility,
+    },
+    live_status::{get_live_statuses, LiveStatus, LiveStatusCache},
+    profile_cache::ProfileCache,
+    service::{get_followed_channels, get_profile_by_username, get_twitch_profile},
+    stream_management::get_access_token,
     DbConn, GlobalConfig,
 };
 
@@ -19,35 +29,90 @@ pub struct FavouriteStreamsRequest {
 
 #[derive(Debug, Serialize)]
 pub struct FavouriteStreamResponse {
+    pub id: i32,
     pub identifier: String,
-    pub source: String,
+    pub source: StreamSource,
+    pub pinned: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub live: Option<LiveStatus>,
 }
 
 impl FavouriteStreamResponse {
     pub fn from(saved_favourited_streamer: SavedFavouriteStreamsModel) -> Self {
         Self {
+            id: saved_favourited_streamer.id,
             identifier: saved_favourited_streamer.identifier,
             source: saved_favourited_streamer.source,
+            pinned: saved_favourited_streamer.pinned,
+            live: None,
         }
     }
 }
 
-#[get("/favourite-streams")]
+fn includes(include: Option<&str>, field: &str) -> bool {
+    include.is_some_and(|i| i.split(',').any(|f| f.trim() == field))
+}
+
+async fn attach_live_statuses(
+    favourites: &mut [FavouriteStreamResponse],
+    live_status_cache: &LiveStatusCache,
+    global_config: &GlobalConfig,
+) {
+    let logins = favourites
+        .iter()
+        .filter(|f| f.source == StreamSource::Twitch)
+        .map(|f| f.identifier.clone())
+        .collect::<Vec<String>>();
+
+    if logins.is_empty() {
+        return;
+    }
+
+    match get_live_statuses(live_status_cache, global_config, logins).await {
+        Ok(statuses) => {
+            for favourite in favourites
+                .iter_mut()
+                .filter(|f| f.source == StreamSource::Twitch)
+            {
+                favourite.live = statuses.get(&favourite.identifier.to_lowercase()).cloned();
+            }
+        }
+        Err(status) => info!("could not fetch live statuses {}", status),
+    }
+}
+
+#[get("/favourite-streams?<include>&<group>")]
 pub async fn get_favourite_streams(
     db_conn: DbConn,
+    include: Option<&str>,
+    group: Option<i32>,
     global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    live_status_cache: &State<LiveStatusCache>,
     access_token: AccessToken,
 ) -> Result<Json<Vec<FavouriteStreamResponse>>, Status> {
     debug!("got token {}", &access_token.0);
-    let profile = get_profile(&access_token.0, &global_config.auth_url).await?;
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
 
-    let all_favourited_streams = find_all_favourited_streamers(&db_conn, profile.id).await?;
+    let all_favourited_streams = match group {
+        Some(group) => {
+            find_favourite_group(&db_conn, profile.id, group).await?;
+            find_favourited_streamers_in_group(&db_conn, profile.id, group).await?
+        }
+        None => find_all_favourited_streamers(&db_conn, profile.id).await?,
+    };
 
-    let response = all_favourited_streams
+    let mut response: Vec<FavouriteStreamResponse> = all_favourited_streams
         .into_iter()
-        .map(|s| FavouriteStreamResponse::from(s))
+        .map(FavouriteStreamResponse::from)
         .collect();
 
+    if includes(include, "live") {
+        attach_live_statuses(&mut response, live_status_cache, global_config).await;
+    }
+
     Ok(Json(response))
 }
 
@@ -56,29 +121,280 @@ pub async fn post_favourite_stream(
     db_conn: DbConn,
     favourite_streams_request: Json<FavouriteStreamsRequest>,
     global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
     access_token: AccessToken,
 ) -> Result<Status, Status> {
-    let profile = get_profile(&access_token.0, &global_config.auth_url).await?;
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let inserted = insert_favourite_streamer(
+        &db_conn,
+        FavouriteStreamsModel::from(favourite_streams_request.into_inner(), profile.id),
+    )
+    .await?;
+
+    match inserted > 0 {
+        true => Ok(Status::Created),
+        false => Err(Status::Conflict),
+    }
+}
+
+#[delete("/favourite-streams", data = "<favourite_streams_request>")]
+pub async fn delete_favourite_stream(
+    db_conn: DbConn,
+    favourite_streams_request: Json<FavouriteStreamsRequest>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
 
     let favourite_stream_unpacked = favourite_streams_request.into_inner();
-    let has_found_conflict = find_favourite_streamer(
+    let deleted = delete_favourite_streamer(
         &db_conn,
         profile.id,
-        favourite_stream_unpacked.identifier.clone(),
-        StreamSource::from(favourite_stream_unpacked.source.clone()),
+        favourite_stream_unpacked.identifier,
+        favourite_stream_unpacked.source,
     )
     .await?;
 
-    match has_found_conflict > 0 {
-        true => Err(Status::Conflict),
-        false => {
-            insert_favourite_streamer(
-                &db_conn,
-                FavouriteStreamsModel::from(favourite_stream_unpacked, profile.id),
-            )
-            .await?;
+    match deleted > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
 
-            Ok(Status::Created)
+#[delete("/favourite-streams/<id>")]
+pub async fn delete_favourite_stream_by_id(
+    db_conn: DbConn,
+    id: i32,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let deleted = delete_favourite_streamer_by_id(&db_conn, profile.id, id).await?;
+
+    match deleted > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub skipped: usize,
+}
+
+/// Needs the Twitch token in `twitch-token` or `platform-token`, next to the BeemStream token
+/// naming whose favourites they become.
+#[post("/favourite-streams/import/twitch")]
+pub async fn import_twitch_follows(
+    db_conn: DbConn,
+    global_config: &State<GlobalConfig>,
+    identity: StreamIdentity,
+) -> Result<Json<ImportResponse>, Status> {
+    let profile_id = identity.profile_id.ok_or(Status::Unauthorized)?;
+    let twitch_access_token = identity.platform_token;
+    let twitch_profile = get_twitch_profile(&get_access_token(&twitch_access_token.0)).await?;
+
+    let mut followed = vec![];
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let page = get_followed_channels(
+            &twitch_access_token.0,
+            &twitch_profile.user_id,
+            &global_config.twitch_client_id,
+            cursor.as_deref(),
+        )
+        .await?;
+
+        followed.extend(page.data.into_iter().map(|f| FavouriteStreamsModel {
+            associated_user: profile_id,
+            identifier: f.broadcaster_login,
+            source: StreamSource::Twitch,
+        }));
+
+        match page.pagination.cursor {
+            Some(next) if !next.is_empty() => cursor = Some(next),
+            _ => break,
         }
     }
+
+    let total = followed.len();
+    let imported = match total {
+        0 => 0,
+        _ => insert_favourite_streamers(&db_conn, profile_id, followed).await?,
+    };
+
+    Ok(Json(ImportResponse {
+        imported,
+        skipped: total - imported,
+    }))
+}
+
+#[put("/favourite-streams/order", data = "<order_request>")]
+pub async fn put_favourite_streams_order(
+    db_conn: DbConn,
+    order_request: Json<Vec<i32>>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    update_favourite_streamers_order(&db_conn, profile.id, order_request.into_inner()).await?;
+
+    Ok(Status::NoContent)
+}
+
+async fn set_favourite_stream_pinned(
+    db_conn: DbConn,
+    id: i32,
+    pinned: bool,
+    global_config: &GlobalConfig,
+    profile_cache: &ProfileCache,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let updated = update_favourite_streamer_pinned(&db_conn, profile.id, id, pinned).await?;
+
+    match updated > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
+
+#[put("/favourite-streams/<id>/pin")]
+pub async fn pin_favourite_stream(
+    db_conn: DbConn,
+    id: i32,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    set_favourite_stream_pinned(
+        db_conn,
+        id,
+        true,
+        global_config,
+        profile_cache,
+        access_token,
+    )
+    .await
+}
+
+#[delete("/favourite-streams/<id>/pin")]
+pub async fn unpin_favourite_stream(
+    db_conn: DbConn,
+    id: i32,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    set_favourite_stream_pinned(
+        db_conn,
+        id,
+        false,
+        global_config,
+        profile_cache,
+        access_token,
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FavouriteStreamsSettings {
+    pub visibility: FavouriteVisibility,
+}
+
+#[get("/favourite-streams/settings")]
+pub async fn get_favourite_streams_settings(
+    db_conn: DbConn,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Json<FavouriteStreamsSettings>, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let visibility = find_favourite_visibility(&db_conn, profile.id).await?;
+
+    Ok(Json(FavouriteStreamsSettings { visibility }))
+}
+
+#[put("/favourite-streams/settings", data = "<settings_request>")]
+pub async fn put_favourite_streams_settings(
+    db_conn: DbConn,
+    settings_request: Json<FavouriteStreamsSettings>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let settings = FavouriteStreamsSettingsModel {
+        associated_user: profile.id,
+        visibility: settings_request.into_inner().visibility,
+    };
+    upsert_favourite_visibility(&db_conn, settings).await?;
+
+    Ok(Status::NoContent)
+}
+
+fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Private lists answer 404 rather than 403 so they can't be told apart from unknown users.
+#[get("/<username>/favourite-streams?<include>")]
+pub async fn get_user_favourite_streams(
+    db_conn: DbConn,
+    username: &str,
+    include: Option<&str>,
+    global_config: &State<GlobalConfig>,
+    live_status_cache: &State<LiveStatusCache>,
+) -> Result<Json<Vec<FavouriteStreamResponse>>, Status> {
+    if !is_valid_username(username) {
+        return Err(Status::NotFound);
+    }
+
+    let profile = get_profile_by_username(username, &global_config.profile_url).await?;
+
+    match find_favourite_visibility(&db_conn, profile.id).await? {
+        FavouriteVisibility::Public | FavouriteVisibility::Unlisted => {}
+        FavouriteVisibility::Private => return Err(Status::NotFound),
+    }
+
+    let all_favourited_streams = find_all_favourited_streamers(&db_conn, profile.id).await?;
+
+    let mut response: Vec<FavouriteStreamResponse> = all_favourited_streams
+        .into_iter()
+        .map(FavouriteStreamResponse::from)
+        .collect();
+
+    if includes(include, "live") {
+        attach_live_statuses(&mut response, live_status_cache, global_config).await;
+    }
+
+    Ok(Json(response))
 }
@@ -0,0 +1,240 @@
+use rocket::{
+    data::{Data, ToByteUnit},
+    get,
+    http::{ContentType, Status},
+    post,
+    serde::json::Json,
+    State,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    authenticate::AccessToken,
+    database::favourite_streams::{
+        find_all_favourited_streamers, import_favourite_streamers, FavouriteStreamsModel,
+        StreamSource,
+    },
+    profile_cache::ProfileCache,
+    DbConn, GlobalConfig,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum TransferFormat {
+    Json,
+    Csv,
+}
+
+impl TransferFormat {
+    pub fn from(format: Option<&str>) -> Result<Self, Status> {
+        match format {
+            None | Some("json") => Ok(TransferFormat::Json),
+            Some("csv") => Ok(TransferFormat::Csv),
+            Some(_) => Err(Status::UnprocessableEntity),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FavouriteStreamRow {
+    pub identifier: String,
+    pub source: String,
+}
+
+#[derive(Debug, Serialize)]
+pub enum ImportRowStatus {
+    Added,
+    Duplicated,
+    Rejected,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRowReport {
+    pub row: usize,
+    pub identifier: Option<String>,
+    pub source: Option<String>,
+    pub status: ImportRowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub added: usize,
+    pub duplicated: usize,
+    pub rejected: usize,
+    pub rows: Vec<ImportRowReport>,
+}
+
+fn parse_rows(
+    format: TransferFormat,
+    body: &str,
+) -> Result<Vec<Result<FavouriteStreamRow, String>>, Status> {
+    match format {
+        TransferFormat::Json => {
+            let rows: Vec<serde_json::Value> =
+                serde_json::from_str(body).map_err(|_| Status::UnprocessableEntity)?;
+
+            Ok(rows
+                .into_iter()
+                .map(|r| serde_json::from_value(r).map_err(|e| e.to_string()))
+                .collect())
+        }
+        TransferFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(body.as_bytes());
+
+            Ok(reader
+                .deserialize()
+                .map(|r| r.map_err(|e: csv::Error| e.to_string()))
+                .collect())
+        }
+    }
+}
+
+fn validate_row(row: &FavouriteStreamRow) -> Result<StreamSource, String> {
+    if row.identifier.trim().is_empty() {
+        return Err("identifier is empty".to_owned());
+    }
+    row.source.parse::<StreamSource>()
+}
+
+#[get("/favourite-streams/export?<format>")]
+pub async fn export_favourite_streams(
+    db_conn: DbConn,
+    format: Option<&str>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<(ContentType, String), Status> {
+    let format = TransferFormat::from(format)?;
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let rows: Vec<FavouriteStreamRow> = find_all_favourited_streamers(&db_conn, profile.id)
+        .await?
+        .into_iter()
+        .map(|s| FavouriteStreamRow {
+            identifier: s.identifier,
+            source: s.source.as_str().to_owned(),
+        })
+        .collect();
+
+    match format {
+        TransferFormat::Json => {
+            let body = serde_json::to_string(&rows).map_err(|_| Status::InternalServerError)?;
+            Ok((ContentType::JSON, body))
+        }
+        TransferFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for row in &rows {
+                writer
+                    .serialize(row)
+                    .map_err(|_| Status::InternalServerError)?;
+            }
+            // an empty export still carries the header so it can be imported back
+            if rows.is_empty() {
+                writer
+                    .write_record(["identifier", "source"])
+                    .map_err(|_| Status::InternalServerError)?;
+            }
+            let body = writer
+                .into_inner()
+                .map_err(|_| Status::InternalServerError)?;
+            let body = String::from_utf8(body).map_err(|_| Status::InternalServerError)?;
+            Ok((ContentType::CSV, body))
+        }
+    }
+}
+
+#[post("/favourite-streams/import?<format>", data = "<body>")]
+pub async fn import_favourite_streams(
+    db_conn: DbConn,
+    format: Option<&str>,
+    body: Data<'_>,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Json<ImportReport>, Status> {
+    let format = TransferFormat::from(format)?;
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let body = body
+        .open(1.mebibytes())
+        .into_string()
+        .await
+        .map_err(|_| Status::BadRequest)?;
+    if !body.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+
+    let parsed_rows = parse_rows(format, &body)?;
+
+    let mut rows = vec![];
+    let mut to_insert = vec![];
+    for (i, parsed_row) in parsed_rows.into_iter().enumerate() {
+        let row = match parsed_row {
+            Ok(row) => row,
+            Err(reason) => {
+                rows.push(ImportRowReport {
+                    row: i + 1,
+                    identifier: None,
+                    source: None,
+                    status: ImportRowStatus::Rejected,
+                    reason: Some(reason),
+                });
+                continue;
+            }
+        };
+
+        let (status, reason) = match validate_row(&row) {
+            Ok(source) => {
+                to_insert.push((
+                    rows.len(),
+                    FavouriteStreamsModel {
+                        associated_user: profile.id,
+                        identifier: row.identifier.trim().to_owned(),
+                        source,
+                    },
+                ));
+                (ImportRowStatus::Added, None)
+            }
+            Err(reason) => (ImportRowStatus::Rejected, Some(reason)),
+        };
+
+        rows.push(ImportRowReport {
+            row: i + 1,
+            identifier: Some(row.identifier),
+            source: Some(row.source),
+            status,
+            reason,
+        });
+    }
+
+    let (report_indexes, streamers): (Vec<usize>, Vec<FavouriteStreamsModel>) =
+        to_insert.into_iter().unzip();
+    let inserted = import_favourite_streamers(&db_conn, profile.id, streamers).await?;
+
+    for (index, was_inserted) in report_indexes.into_iter().zip(inserted) {
+        if !was_inserted {
+            rows[index].status = ImportRowStatus::Duplicated;
+        }
+    }
+
+    let (mut added, mut duplicated, mut rejected) = (0, 0, 0);
+    for row in &rows {
+        match row.status {
+            ImportRowStatus::Added => added += 1,
+            ImportRowStatus::Duplicated => duplicated += 1,
+            ImportRowStatus::Rejected => rejected += 1,
+        }
+    }
+
+    Ok(Json(ImportReport {
+        added,
+        duplicated,
+        rejected,
+        rows,
+    }))
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use rocket::{delete, get, http::Status, post, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+    authenticate::{AccessToken, StreamIdentity},
+    database::{
+        favourite_streams::StreamSource,
+        linked_account::{
+            delete_linked_account, find_linked_accounts, link_account, LinkedAccountModel,
+            SavedLinkedAccountModel,
+        },
+    },
+    profile_cache::ProfileCache,
+    stream_platform::stream_platform,
+    DbConn, GlobalConfig,
+};
+
+#[derive(Debug, Serialize)]
+pub struct LinkedAccount {
+    pub provider: StreamSource,
+    pub platform_user_id: String,
+    pub linked_at: DateTime<Utc>,
+}
+
+impl LinkedAccount {
+    pub fn from(account: SavedLinkedAccountModel) -> Self {
+        Self {
+            provider: account.provider,
+            platform_user_id: account.platform_user_id,
+            linked_at: account.linked_at,
+        }
+    }
+}
+
+/// The platform accounts whose presets belong to the caller.
+#[get("/linked-accounts")]
+pub async fn get_linked_accounts(
+    db_conn: DbConn,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+) -> Result<Json<Vec<LinkedAccount>>, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+
+    let accounts = find_linked_accounts(&db_conn, profile.id)
+        .await?
+        .into_iter()
+        .map(LinkedAccount::from)
+        .collect();
+
+    Ok(Json(accounts))
+}
+
+/// Links the account behind the platform token to the caller's profile, which then owns the
+/// presets, history and schedules saved for it. `Conflict` when another profile has it.
+#[post("/linked-accounts?<provider>")]
+pub async fn post_linked_account(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<(Status, Json<LinkedAccount>), Status> {
+    let profile_id = identity.profile_id.ok_or(Status::Unauthorized)?;
+    let platform = stream_platform(provider, global_config)?;
+    let user_id = platform.get_user_id(&identity.platform_token.0).await?;
+
+    let linked = link_account(
+        &db_conn,
+        LinkedAccountModel {
+            associated_profile: profile_id,
+            provider: platform.source(),
+            platform_user_id: user_id,
+        },
+    )
+    .await?;
+
+    Ok((Status::Created, Json(LinkedAccount::from(linked))))
+}
+
+/// Unlinks the caller's account on `provider` so another profile can link it.
+#[delete("/linked-accounts/<provider>")]
+pub async fn delete_linked_account_by_provider(
+    db_conn: DbConn,
+    global_config: &State<GlobalConfig>,
+    profile_cache: &State<ProfileCache>,
+    access_token: AccessToken,
+    provider: &str,
+) -> Result<Status, Status> {
+    let profile = access_token
+        .profile(profile_cache, &global_config.auth_url)
+        .await?;
+    let platform = stream_platform(Some(provider), global_config)?;
+
+    match delete_linked_account(&db_conn, profile.id, platform.source()).await? > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
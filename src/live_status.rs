@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{http::Status, info};
+use serde::Serialize;
+
+use crate::{
+    service::{get_app_access_token, get_streams},
+    GlobalConfig,
+};
+
+const CACHE_TTL: Duration = Duration::from_secs(60);
+const HELIX_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveStatus {
+    pub is_live: bool,
+    pub viewer_count: Option<u32>,
+    pub game_name: Option<String>,
+    pub title: Option<String>,
+}
+
+impl LiveStatus {
+    fn offline() -> Self {
+        Self {
+            is_live: false,
+            viewer_count: None,
+            game_name: None,
+            title: None,
+        }
+    }
+}
+
+/// Short lived cache of Twitch live statuses keyed by lowercase login, held in managed state.
+#[derive(Default)]
+pub struct LiveStatusCache {
+    statuses: Mutex<HashMap<String, (Instant, LiveStatus)>>,
+    app_token: Mutex<Option<(Instant, String)>>,
+}
+
+impl LiveStatusCache {
+    fn cached(&self, logins: &[String]) -> (HashMap<String, LiveStatus>, Vec<String>) {
+        let statuses = self.statuses.lock().unwrap();
+        let mut hits = HashMap::new();
+        let mut misses = vec![];
+
+        for login in logins {
+            match statuses.get(login) {
+                Some((fetched_at, status)) if fetched_at.elapsed() < CACHE_TTL => {
+                    hits.insert(login.clone(), status.clone());
+                }
+                _ => misses.push(login.clone()),
+            }
+        }
+
+        (hits, misses)
+    }
+
+    fn store(&self, fetched: &HashMap<String, LiveStatus>) {
+        let mut statuses = self.statuses.lock().unwrap();
+        statuses.retain(|_, (fetched_at, _)| fetched_at.elapsed() < CACHE_TTL);
+
+        let now = Instant::now();
+        for (login, status) in fetched {
+            statuses.insert(login.clone(), (now, status.clone()));
+        }
+    }
+
+    async fn app_token(&self, global_config: &GlobalConfig) -> Result<String, Status> {
+        if let Some((expires_at, token)) = self.app_token.lock().unwrap().as_ref() {
+            if Instant::now() < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let app_token = get_app_access_token(
+            &global_config.twitch_client_id,
+            &global_config.twitch_client_secret,
+        )
+        .await?;
+
+        let bearer = format!("Bearer {}", app_token.access_token);
+        let expires_at =
+            Instant::now() + Duration::from_secs(app_token.expires_in).saturating_sub(CACHE_TTL);
+        *self.app_token.lock().unwrap() = Some((expires_at, bearer.clone()));
+
+        Ok(bearer)
+    }
+}
+
+/// Resolves the live status of every login, only asking Helix for logins not already cached.
+pub async fn get_live_statuses(
+    cache: &LiveStatusCache,
+    global_config: &GlobalConfig,
+    logins: Vec<String>,
+) -> Result<HashMap<String, LiveStatus>, Status> {
+    let logins: Vec<String> = logins.into_iter().map(|l| l.to_lowercase()).collect();
+    let (mut statuses, misses) = cache.cached(&logins);
+
+    if misses.is_empty() {
+        return Ok(statuses);
+    }
+
+    let access_token = cache.app_token(global_config).await?;
+    let mut fetched = HashMap::new();
+
+    for batch in misses.chunks(HELIX_BATCH_SIZE) {
+        for login in batch {
+            fetched.insert(login.clone(), LiveStatus::offline());
+        }
+
+        let streams = get_streams(&access_token, &global_config.twitch_client_id, batch).await?;
+        info!("fetched {} live streams of {}", streams.len(), batch.len());
+
+        for stream in streams {
+            fetched.insert(
+                stream.user_login.to_lowercase(),
+                LiveStatus {
+                    is_live: true,
+                    viewer_count: Some(stream.viewer_count),
+                    game_name: Some(stream.game_name),
+                    title: Some(stream.title),
+                },
+            );
+        }
+    }
+
+    cache.store(&fetched);
+    statuses.extend(fetched);
+
+    Ok(statuses)
+}
@@ -1,45 +1,138 @@
 #[macro_use]
 extern crate diesel;
 
-use favourite_streams::{get_favourite_streams, post_favourite_stream};
+use authenticate::TokenVerifier;
+use favourite_groups::{
+    delete_favourite_group_by_id, delete_favourite_group_member_by_id, get_favourite_groups,
+    post_favourite_group, put_favourite_group, put_favourite_group_member,
+};
+use favourite_streams::{
+    delete_favourite_stream, delete_favourite_stream_by_id, get_favourite_streams,
+    get_favourite_streams_settings, get_user_favourite_streams, import_twitch_follows,
+    pin_favourite_stream, post_favourite_stream, put_favourite_streams_order,
+    put_favourite_streams_settings, unpin_favourite_stream,
+};
+use favourite_streams_transfer::{export_favourite_streams, import_favourite_streams};
+use linked_accounts::{
+    delete_linked_account_by_provider, get_linked_accounts, post_linked_account,
+};
+use live_status::LiveStatusCache;
+use profile_cache::{get_profile_cache_metrics, ProfileCache};
 use rocket::{launch, routes, Build};
 use rocket_sync_db_pools::database;
 use rocket_sync_db_pools::diesel::PgConnection;
+use scheduled_presets::{
+    delete_scheduled_preset_by_id, get_scheduled_presets, post_scheduled_preset,
+    put_scheduled_preset, scheduled_preset_worker,
+};
 use serde::Deserialize;
-use stream_management::{get_stream_management, post_stream_management, put_stream_management};
+use stream_catalogue::{get_stream_tags, stream_catalogue_worker, StreamCatalogueCache};
+use stream_management::{
+    delete_stream_management, get_stream_categories, get_stream_management,
+    get_stream_management_preview, post_stream_management, post_stream_preset, post_title_render,
+    put_stream_management, update_stream_management,
+};
+use stream_management_history::{get_stream_management_history, revert_stream_management};
+use twitch_credentials::{
+    delete_twitch_link, get_twitch_link, post_twitch_link, TwitchCredentials,
+};
 
 pub mod authenticate;
 pub mod database;
+mod favourite_groups;
 mod favourite_streams;
+mod favourite_streams_transfer;
+mod linked_accounts;
+mod live_status;
+mod profile_cache;
+mod scheduled_presets;
 pub mod schema;
 pub mod service;
+mod stream_catalogue;
 mod stream_management;
+mod stream_management_history;
+mod stream_platform;
+mod title_template;
+mod twitch_credentials;
 
 #[database("pg_conn")]
 pub struct DbConn(PgConnection);
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct GlobalConfig {
     auth_url: String,
+    profile_url: String,
     twitch_client_id: String,
+    twitch_client_secret: String,
+    twitch_redirect_uri: String,
+    jwt_secret: Option<String>,
+    jwks_path: Option<String>,
+    jwt_audience: Option<String>,
+    metrics_token: Option<String>,
 }
 
 #[launch]
 fn rocket() -> rocket::Rocket<Build> {
     let rocket = rocket::build();
     let global_config: GlobalConfig = rocket.figment().extract().expect("global config");
+    let twitch_credentials = TwitchCredentials::from(rocket.figment());
+    let token_verifier = TokenVerifier::from(&global_config);
 
     rocket
         .attach(DbConn::fairing())
         .manage(global_config)
+        .manage(LiveStatusCache::default())
+        .manage(ProfileCache::default())
+        .manage(twitch_credentials)
+        .manage(token_verifier)
+        .manage(StreamCatalogueCache::default())
+        .attach(scheduled_preset_worker())
+        .attach(stream_catalogue_worker())
         .mount(
             "/stream-config",
             routes![
                 post_favourite_stream,
                 get_favourite_streams,
+                delete_favourite_stream,
+                delete_favourite_stream_by_id,
+                put_favourite_streams_order,
+                import_twitch_follows,
+                export_favourite_streams,
+                import_favourite_streams,
+                pin_favourite_stream,
+                unpin_favourite_stream,
+                get_favourite_streams_settings,
+                put_favourite_streams_settings,
+                get_user_favourite_streams,
+                get_favourite_groups,
+                post_favourite_group,
+                put_favourite_group,
+                delete_favourite_group_by_id,
+                put_favourite_group_member,
+                delete_favourite_group_member_by_id,
                 post_stream_management,
+                post_stream_preset,
                 get_stream_management,
-                put_stream_management
+                put_stream_management,
+                update_stream_management,
+                delete_stream_management,
+                get_stream_categories,
+                get_stream_management_preview,
+                post_title_render,
+                get_stream_tags,
+                get_stream_management_history,
+                revert_stream_management,
+                get_scheduled_presets,
+                post_scheduled_preset,
+                put_scheduled_preset,
+                delete_scheduled_preset_by_id,
+                get_twitch_link,
+                post_twitch_link,
+                delete_twitch_link,
+                get_profile_cache_metrics,
+                get_linked_accounts,
+                post_linked_account,
+                delete_linked_account_by_provider
             ],
         )
 }
@@ -0,0 +1,194 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use rocket::{
+    get,
+    http::Status,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, State,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    service::{get_profile, Profile},
+    GlobalConfig,
+};
+
+const PROFILE_TTL: Duration = Duration::from_secs(60);
+const UNAUTHORIZED_TTL: Duration = Duration::from_secs(15);
+const MAX_CACHED_PROFILES: usize = 10_000;
+
+type TokenHash = [u8; 32];
+
+#[derive(Default)]
+struct CachedProfiles {
+    profiles: HashMap<TokenHash, (Instant, Option<i32>)>,
+    /// The same entries ordered by when they expire, so the next to go is always first.
+    expiries: BTreeSet<(Instant, TokenHash)>,
+}
+
+/// Profile ids from `auth_url` keyed by a hash of the token, so tokens aren't kept in memory.
+/// Rejected tokens are remembered briefly too, held in managed state.
+#[derive(Default)]
+pub struct ProfileCache {
+    profiles: Mutex<CachedProfiles>,
+    hits: AtomicU64,
+    unauthorized_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileCacheMetrics {
+    pub hits: u64,
+    pub unauthorized_hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub entries: usize,
+}
+
+fn token_hash(access_token: &str) -> TokenHash {
+    Sha256::digest(access_token.as_bytes()).into()
+}
+
+impl ProfileCache {
+    fn cached(&self, key: &TokenHash) -> Option<Option<i32>> {
+        match self.profiles.lock().unwrap().profiles.get(key) {
+            Some((expires_at, profile_id)) if Instant::now() < *expires_at => Some(*profile_id),
+            _ => None,
+        }
+    }
+
+    /// Drops expired entries, then makes room by dropping the ones closest to expiring.
+    fn store(&self, key: TokenHash, profile_id: Option<i32>, ttl: Duration) {
+        let mut cached = self.profiles.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some((expires_at, _)) = cached.profiles.remove(&key) {
+            cached.expiries.remove(&(expires_at, key));
+        }
+        while let Some(&(expires_at, oldest)) = cached.expiries.iter().next() {
+            if now < expires_at && cached.profiles.len() < MAX_CACHED_PROFILES {
+                break;
+            }
+            cached.expiries.remove(&(expires_at, oldest));
+            cached.profiles.remove(&oldest);
+        }
+
+        let expires_at = now + ttl;
+        cached.profiles.insert(key, (expires_at, profile_id));
+        cached.expiries.insert((expires_at, key));
+    }
+
+    /// `get_profile` behind the cache, only a 401 is cached as a failure.
+    pub async fn get_profile(&self, access_token: &str, auth_url: &str) -> Result<Profile, Status> {
+        let key = token_hash(access_token);
+
+        match self.cached(&key) {
+            Some(Some(id)) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Profile { id });
+            }
+            Some(None) => {
+                self.unauthorized_hits.fetch_add(1, Ordering::Relaxed);
+                return Err(Status::Unauthorized);
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        match get_profile(access_token, auth_url).await {
+            Ok(profile) => {
+                self.store(key, Some(profile.id), PROFILE_TTL);
+                Ok(profile)
+            }
+            Err(status) if status == Status::Unauthorized => {
+                self.store(key, None, UNAUTHORIZED_TTL);
+                Err(status)
+            }
+            Err(status) => Err(status),
+        }
+    }
+
+    pub fn metrics(&self) -> ProfileCacheMetrics {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let unauthorized_hits = self.unauthorized_hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + unauthorized_hits + misses;
+
+        ProfileCacheMetrics {
+            hits,
+            unauthorized_hits,
+            misses,
+            hit_rate: match lookups {
+                0 => 0.0,
+                _ => (hits + unauthorized_hits) as f64 / lookups as f64,
+            },
+            entries: self.profiles.lock().unwrap().profiles.len(),
+        }
+    }
+}
+
+/// Internal callers such as monitoring, they send the configured `metrics_token` in a
+/// `metrics-token` header. Without one configured nobody is let in.
+pub struct MetricsAccess;
+
+#[async_trait]
+impl<'r> FromRequest<'r> for MetricsAccess {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = request
+            .rocket()
+            .state::<GlobalConfig>()
+            .and_then(|global_config| global_config.metrics_token.as_deref());
+        let sent = request.headers().get_one("metrics-token");
+
+        // compared as hashes so the comparison doesn't leak how much of the token matched
+        match (expected, sent) {
+            (Some(expected), Some(sent)) if token_hash(expected) == token_hash(sent) => {
+                Outcome::Success(MetricsAccess)
+            }
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+#[get("/profile-cache/metrics")]
+pub async fn get_profile_cache_metrics(
+    _metrics_access: MetricsAccess,
+    profile_cache: &State<ProfileCache>,
+) -> Json<ProfileCacheMetrics> {
+    Json(profile_cache.metrics())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_entry_closest_to_expiring_when_full() {
+        let cache = ProfileCache::default();
+        for id in 0..MAX_CACHED_PROFILES as i32 {
+            let ttl = PROFILE_TTL + Duration::from_secs(id as u64);
+            cache.store(token_hash(&id.to_string()), Some(id), ttl);
+        }
+
+        cache.store(token_hash("newest"), Some(-1), PROFILE_TTL);
+
+        let metrics = cache.metrics();
+        assert_eq!(metrics.entries, MAX_CACHED_PROFILES);
+        assert_eq!(cache.cached(&token_hash("0")), None);
+        assert_eq!(cache.cached(&token_hash("1")), Some(Some(1)));
+        assert_eq!(cache.cached(&token_hash("newest")), Some(Some(-1)));
+    }
+}
@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    delete, error, fairing::AdHoc, get, http::Status, info, post, put, serde::json::Json, tokio,
+    State,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    authenticate::StreamIdentity,
+    database::{
+        favourite_streams::StreamSource,
+        linked_account::find_linked_profile,
+        scheduled_preset::{
+            claim_due_scheduled_presets, delete_scheduled_preset, find_scheduled_presets,
+            insert_scheduled_preset, update_scheduled_preset, update_scheduled_preset_run,
+            SavedScheduledPresetModel, ScheduledPresetModel, ScheduledPresetRunModel,
+        },
+        stream_management::find_stream_title,
+        twitch_credential::find_twitch_credential,
+        worker_database,
+    },
+    stream_management::{apply_stream_preset, get_platform_user, ApplyOutcome, StreamUser},
+    stream_platform::TwitchPlatform,
+    twitch_credentials::{get_twitch_access_token, TwitchCredentials},
+    DbConn, GlobalConfig,
+};
+
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduledPresetRequest {
+    pub preset_id: i32,
+    pub run_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledPreset {
+    pub id: i32,
+    pub preset_id: i32,
+    pub run_at: DateTime<Utc>,
+    pub ran_at: Option<DateTime<Utc>>,
+    pub outcome: Option<ApplyOutcome>,
+    pub application_id: Option<i32>,
+}
+
+impl ScheduledPreset {
+    pub fn from(schedule: SavedScheduledPresetModel) -> Self {
+        Self {
+            id: schedule.id,
+            preset_id: schedule.associated_title,
+            run_at: schedule.run_at,
+            ran_at: schedule.ran_at,
+            outcome: schedule.applied.map(|applied| ApplyOutcome {
+                applied,
+                platform_status: schedule.platform_status.map(|s| s as u16),
+            }),
+            application_id: schedule.associated_application,
+        }
+    }
+}
+
+/// Checks the Twitch preset belongs to the user, the time is still ahead and a Twitch account is
+/// linked to run it with, before anything is saved. Only Twitch has linked credentials so far.
+async fn schedule_model(
+    db_conn: &DbConn,
+    user: StreamUser,
+    request: ScheduledPresetRequest,
+) -> Result<ScheduledPresetModel, Status> {
+    if request.run_at <= Utc::now() {
+        return Err(Status::UnprocessableEntity);
+    }
+    find_stream_title(
+        db_conn,
+        request.preset_id,
+        user.clone(),
+        StreamSource::Twitch,
+    )
+    .await?;
+    find_twitch_credential(db_conn, user.user_id.clone())
+        .await?
+        .ok_or(Status::PreconditionRequired)?;
+
+    Ok(ScheduledPresetModel {
+        associated_user: user.user_id,
+        associated_title: request.preset_id,
+        run_at: request.run_at,
+        associated_profile: user.profile_id,
+    })
+}
+
+/// Schedules are only run against Twitch, the one platform with linked credentials.
+async fn get_twitch_user(
+    db_conn: &DbConn,
+    identity: &StreamIdentity,
+    global_config: &GlobalConfig,
+) -> Result<StreamUser, Status> {
+    let (_, user) = get_platform_user(db_conn, identity, Some("twitch"), global_config).await?;
+    Ok(user)
+}
+
+#[get("/stream-management/schedule")]
+pub async fn get_scheduled_presets(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    global_config: &State<GlobalConfig>,
+) -> Result<Json<Vec<ScheduledPreset>>, Status> {
+    let user = get_twitch_user(&db_conn, &identity, global_config).await?;
+
+    let schedules = find_scheduled_presets(&db_conn, user)
+        .await?
+        .into_iter()
+        .map(ScheduledPreset::from)
+        .collect();
+
+    Ok(Json(schedules))
+}
+
+#[post("/stream-management/schedule", data = "<scheduled_preset_request>")]
+pub async fn post_scheduled_preset(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    global_config: &State<GlobalConfig>,
+    scheduled_preset_request: Json<ScheduledPresetRequest>,
+) -> Result<(Status, Json<ScheduledPreset>), Status> {
+    let user = get_twitch_user(&db_conn, &identity, global_config).await?;
+
+    let schedule = schedule_model(&db_conn, user, scheduled_preset_request.into_inner()).await?;
+    let saved = insert_scheduled_preset(&db_conn, schedule).await?;
+
+    Ok((Status::Created, Json(ScheduledPreset::from(saved))))
+}
+
+// ranked after `/stream-management/<preset_id>/set`, which forwards here as "schedule" isn't an id
+#[put(
+    "/stream-management/schedule/<id>",
+    data = "<scheduled_preset_request>",
+    rank = 2
+)]
+pub async fn put_scheduled_preset(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    global_config: &State<GlobalConfig>,
+    id: i32,
+    scheduled_preset_request: Json<ScheduledPresetRequest>,
+) -> Result<Json<ScheduledPreset>, Status> {
+    let user = get_twitch_user(&db_conn, &identity, global_config).await?;
+
+    let schedule = schedule_model(&db_conn, user, scheduled_preset_request.into_inner()).await?;
+    let saved = update_scheduled_preset(&db_conn, id, schedule).await?;
+
+    Ok(Json(ScheduledPreset::from(saved)))
+}
+
+#[delete("/stream-management/schedule/<id>")]
+pub async fn delete_scheduled_preset_by_id(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    global_config: &State<GlobalConfig>,
+    id: i32,
+) -> Result<Status, Status> {
+    let user = get_twitch_user(&db_conn, &identity, global_config).await?;
+
+    match delete_scheduled_preset(&db_conn, id, user).await? > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
+
+/// Who a schedule runs as and their Twitch token. An account moved to another profile or unlinked
+/// since scheduling can't run it, schedules made before linking run as the account's profile.
+async fn run_as(
+    db_conn: &DbConn,
+    credentials: &TwitchCredentials,
+    global_config: &GlobalConfig,
+    schedule: &SavedScheduledPresetModel,
+) -> Result<(StreamUser, String), Status> {
+    let linked_profile = find_linked_profile(
+        db_conn,
+        StreamSource::Twitch,
+        schedule.associated_user.clone(),
+    )
+    .await?;
+    let profile_id = match schedule.associated_profile {
+        Some(profile_id) if linked_profile != Some(profile_id) => {
+            return Err(Status::PreconditionRequired)
+        }
+        _ => linked_profile,
+    };
+    let access_token = get_twitch_access_token(
+        db_conn,
+        credentials,
+        global_config,
+        &schedule.associated_user,
+    )
+    .await?;
+
+    let user = StreamUser {
+        profile_id,
+        user_id: schedule.associated_user.clone(),
+    };
+    Ok((user, access_token))
+}
+
+async fn run_scheduled_preset(
+    db_conn: &DbConn,
+    credentials: &TwitchCredentials,
+    global_config: &GlobalConfig,
+    schedule: SavedScheduledPresetModel,
+) -> Result<(), Status> {
+    let applied = match run_as(db_conn, credentials, global_config, &schedule).await {
+        Ok((user, access_token)) => {
+            let platform = TwitchPlatform {
+                client_id: global_config.twitch_client_id.clone(),
+            };
+
+            apply_stream_preset(
+                db_conn,
+                &platform,
+                &access_token,
+                &user,
+                schedule.associated_title,
+            )
+            .await
+        }
+        Err(status) => Err(status),
+    };
+
+    // failures before Twitch was reached, such as an unlinked account, never make it into the history
+    let run = match applied {
+        Ok((outcome, application)) => ScheduledPresetRunModel {
+            applied: Some(outcome.applied),
+            platform_status: outcome.platform_status.map(i32::from),
+            associated_application: Some(application.id),
+        },
+        Err(status) => ScheduledPresetRunModel {
+            applied: Some(false),
+            platform_status: Some(i32::from(status.code)),
+            associated_application: None,
+        },
+    };
+    info!(
+        "scheduled preset {} ran, applied: {:?}",
+        schedule.id, run.applied
+    );
+
+    update_scheduled_preset_run(db_conn, schedule.id, run).await?;
+
+    Ok(())
+}
+
+async fn run_due_scheduled_presets(
+    db_conn: &DbConn,
+    credentials: &TwitchCredentials,
+    global_config: &GlobalConfig,
+) -> Result<(), Status> {
+    for schedule in claim_due_scheduled_presets(db_conn).await? {
+        let id = schedule.id;
+        if let Err(status) =
+            run_scheduled_preset(db_conn, credentials, global_config, schedule).await
+        {
+            error!("recording scheduled preset {} failed: {}", id, status);
+        }
+    }
+
+    Ok(())
+}
+
+/// Polls for due schedules in the background for as long as the server is up.
+pub fn scheduled_preset_worker() -> AdHoc {
+    AdHoc::on_liftoff("Scheduled preset worker", |rocket| {
+        Box::pin(async move {
+            let database = match worker_database(rocket).await {
+                Some(database) => database,
+                None => return,
+            };
+            let (global_config, credentials) = match (
+                rocket.state::<GlobalConfig>(),
+                rocket.state::<TwitchCredentials>(),
+            ) {
+                (Some(global_config), Some(credentials)) => {
+                    (global_config.clone(), credentials.clone())
+                }
+                _ => return,
+            };
+            let mut shutdown = rocket.shutdown();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(SCHEDULE_POLL_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            let db_conn = match DbConn::get_one(&database).await {
+                                Some(db_conn) => db_conn,
+                                None => continue,
+                            };
+                            if let Err(status) = run_due_scheduled_presets(&db_conn, &credentials, &global_config).await {
+                                error!("running scheduled presets failed: {}", status);
+                            }
+                        }
+                        _ = &mut shutdown => break,
+                    }
+                }
+            });
+        })
+    })
+}
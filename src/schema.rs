@@ -1,9 +1,106 @@
 table! {
+    favourite_group (id) {
+        id -> Int4,
+        associated_user -> Int4,
+        name -> Varchar,
+    }
+}
+
+table! {
+    favourite_group_member (associated_group, associated_favourite) {
+        associated_group -> Int4,
+        associated_favourite -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::favourite_streams::StreamSourceType;
+
     favourite_streams (id) {
         id -> Int4,
         associated_user -> Int4,
         identifier -> Varchar,
-        source -> Varchar,
+        source -> StreamSourceType,
+        position -> Int4,
+        pinned -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::favourite_streams_settings::FavouriteVisibilityType;
+
+    favourite_streams_settings (associated_user) {
+        associated_user -> Int4,
+        visibility -> FavouriteVisibilityType,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::favourite_streams::StreamSourceType;
+
+    linked_account (provider, platform_user_id) {
+        associated_profile -> Int4,
+        provider -> StreamSourceType,
+        platform_user_id -> Varchar,
+        linked_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::favourite_streams::StreamSourceType;
+
+    preset_application (id) {
+        id -> Int4,
+        associated_user -> Varchar,
+        associated_title -> Nullable<Int4>,
+        reverted_application -> Nullable<Int4>,
+        applied_at -> Timestamptz,
+        previous_state -> Jsonb,
+        applied_state -> Jsonb,
+        applied -> Bool,
+        platform_status -> Nullable<Int4>,
+        provider -> StreamSourceType,
+        associated_profile -> Nullable<Int4>,
+    }
+}
+
+table! {
+    preset_counter (associated_title, name) {
+        associated_title -> Int4,
+        name -> Varchar,
+        value -> Int4,
+    }
+}
+
+table! {
+    scheduled_preset (id) {
+        id -> Int4,
+        associated_user -> Varchar,
+        associated_title -> Int4,
+        run_at -> Timestamptz,
+        ran_at -> Nullable<Timestamptz>,
+        applied -> Nullable<Bool>,
+        platform_status -> Nullable<Int4>,
+        associated_application -> Nullable<Int4>,
+        associated_profile -> Nullable<Int4>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::database::favourite_streams::StreamSourceType;
+
+    stream_catalogue (provider, locale, id) {
+        provider -> StreamSourceType,
+        locale -> Varchar,
+        id -> Varchar,
+        name -> Varchar,
+        box_art_url -> Nullable<Varchar>,
+        fetched_at -> Timestamptz,
     }
 }
 
@@ -11,19 +108,57 @@ table! {
     stream_tag (id) {
         id -> Int4,
         associated_title -> Int4,
-        source_id -> Varchar,
+        source_id -> Nullable<Varchar>,
         name -> Varchar,
     }
 }
 
 table! {
+    use diesel::sql_types::*;
+    use crate::database::favourite_streams::StreamSourceType;
+
     stream_title (id) {
         id -> Int4,
         associated_user -> Varchar,
         title -> Varchar,
+        game_id -> Nullable<Varchar>,
+        game_name -> Nullable<Varchar>,
+        broadcaster_language -> Nullable<Varchar>,
+        provider -> StreamSourceType,
+        description -> Nullable<Varchar>,
+        timezone -> Nullable<Varchar>,
+        associated_profile -> Nullable<Int4>,
+    }
+}
+
+table! {
+    twitch_credential (associated_user) {
+        associated_user -> Varchar,
+        access_token -> Varchar,
+        refresh_token -> Varchar,
+        expires_at -> Timestamptz,
     }
 }
 
+joinable!(favourite_group_member -> favourite_group (associated_group));
+joinable!(favourite_group_member -> favourite_streams (associated_favourite));
+joinable!(preset_application -> stream_title (associated_title));
+joinable!(preset_counter -> stream_title (associated_title));
+joinable!(scheduled_preset -> preset_application (associated_application));
+joinable!(scheduled_preset -> stream_title (associated_title));
 joinable!(stream_tag -> stream_title (associated_title));
 
-allow_tables_to_appear_in_same_query!(favourite_streams, stream_tag, stream_title,);
+allow_tables_to_appear_in_same_query!(
+    favourite_group,
+    favourite_group_member,
+    favourite_streams,
+    favourite_streams_settings,
+    linked_account,
+    preset_application,
+    preset_counter,
+    scheduled_preset,
+    stream_catalogue,
+    stream_tag,
+    stream_title,
+    twitch_credential,
+);
@@ -1,5 +1,5 @@
 use isahc::{http::StatusCode, AsyncReadResponseExt, Request};
-use rocket::http::Status;
+use rocket::http::{RawStr, Status};
 use rocket::info;
 use serde::{Deserialize, Serialize};
 
@@ -16,15 +16,44 @@ pub async fn get_profile(access_token: &str, url: &str) -> Result<Profile, Statu
         .body(())
         .unwrap();
 
-    let mut response = isahc::send_async(request).await.unwrap();
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    // anything but a rejection is the auth service failing, not the token, and mustn't be cached as one
+    match response.status() {
+        StatusCode::OK => response.json().await.map_err(|_| Status::BadGateway),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            info!("failed to authenticate profile {}", response.status());
+            Err(Status::Unauthorized)
+        }
+        status => {
+            info!("auth service failed with {}", status);
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+pub async fn get_profile_by_username(username: &str, url: &str) -> Result<Profile, Status> {
+    let request = isahc::Request::builder()
+        .uri(format!("{}/{}", url.trim_end_matches('/'), username))
+        .method("GET")
+        .body(())
+        .unwrap();
+
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
 
     if response.status() != StatusCode::OK {
-        info!("failed to authenticate profile {}", response.status());
-        return Err(Status::Unauthorized);
+        info!("profile {} not found {}", username, response.status());
+        return Err(Status::NotFound);
     }
 
-    let json = response.json().await.unwrap();
-    Ok(json)
+    response
+        .json()
+        .await
+        .map_err(|_| Status::InternalServerError)
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,13 +73,15 @@ pub async fn get_twitch_profile(access_token: &str) -> Result<TwitchUser, Status
         .body(())
         .unwrap();
 
-    let mut response = isahc::send_async(request).await.unwrap();
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
 
     if response.status() != StatusCode::OK {
         info!("user not found failed with {:?}", response.status());
         return Err(Status::NotFound);
     }
-    Ok(response.json().await.unwrap())
+    response.json().await.map_err(|_| Status::BadGateway)
 }
 
 #[derive(Debug, Deserialize)]
@@ -60,6 +91,10 @@ pub struct TwitchChannelInformation {
     pub broadcaster_language: Option<String>,
     pub game_id: Option<String>,
     pub game_name: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -84,14 +119,16 @@ pub async fn get_channel_information(
         .body(())
         .unwrap();
 
-    let mut response = isahc::send_async(request).await.unwrap();
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
 
     if response.status() != StatusCode::OK {
         info!("user not found failed with {:?}", response.status());
         return Err(Status::NotFound);
     }
-    let mut data: ChannelInformationResponse = response.json().await.unwrap();
-    Ok(data.data.remove(0))
+    let data: ChannelInformationResponse = response.json().await.map_err(|_| Status::BadGateway)?;
+    data.data.into_iter().next().ok_or(Status::NotFound)
 }
 
 #[derive(Debug, Serialize)]
@@ -99,6 +136,8 @@ pub struct ModifyChannelRequest {
     pub game_id: Option<String>,
     pub broadcaster_language: Option<String>,
     pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
 }
 
 pub async fn modify_channel_information(
@@ -119,69 +158,438 @@ pub async fn modify_channel_information(
         .body(serde_json::to_string(&request).unwrap())
         .unwrap();
 
-    let response = isahc::send_async(request).await.unwrap();
+    let response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
 
-    if response.status() != StatusCode::OK {
-        info!("user not found failed with {:?}", response.status());
-        return Err(Status::NotFound);
+    if !response.status().is_success() {
+        info!("twitch rejected change with {:?}", response.status());
+        return Err(Status::from_code(response.status().as_u16()).unwrap_or(Status::BadGateway));
     }
     Ok(Status::NoContent)
 }
 
-#[derive(Debug, Serialize)]
-pub struct ReplaceTagsRequest {
-    pub tag_ids: Vec<String>,
+#[derive(Debug, Deserialize)]
+pub struct TwitchAppToken {
+    pub access_token: String,
+    pub expires_in: u64,
+}
+
+pub async fn get_app_access_token(
+    client_id: &str,
+    client_secret: &str,
+) -> Result<TwitchAppToken, Status> {
+    let request = Request::builder()
+        .uri(format!(
+            "https://id.twitch.tv/oauth2/token?client_id={}&client_secret={}&grant_type=client_credentials",
+            client_id, client_secret
+        ))
+        .method("POST")
+        .body(())
+        .unwrap();
+
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    if response.status() != StatusCode::OK {
+        info!("app access token failed with {:?}", response.status());
+        return Err(Status::ServiceUnavailable);
+    }
+    response
+        .json()
+        .await
+        .map_err(|_| Status::ServiceUnavailable)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwitchStream {
+    pub user_login: String,
+    pub game_name: String,
+    pub title: String,
+    pub viewer_count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamsResponse {
+    data: Vec<TwitchStream>,
 }
 
-pub async fn replace_stream_tags_empty(
+/// Fetches the live streams for up to 100 logins, offline channels are simply absent.
+pub async fn get_streams(
+    access_token: &str,
+    client_id: &str,
+    logins: &[String],
+) -> Result<Vec<TwitchStream>, Status> {
+    let query = logins
+        .iter()
+        .map(|l| format!("user_login={}", RawStr::new(l).percent_encode()))
+        .collect::<Vec<String>>()
+        .join("&");
+
+    let request = Request::builder()
+        .uri(format!(
+            "https://api.twitch.tv/helix/streams?first=100&{}",
+            query
+        ))
+        .method("GET")
+        .header("Authorization", access_token)
+        .header("Client-Id", client_id)
+        .body(())
+        .map_err(|_| Status::BadRequest)?;
+
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    if response.status() != StatusCode::OK {
+        info!("streams lookup failed with {:?}", response.status());
+        return Err(Status::ServiceUnavailable);
+    }
+    let data: StreamsResponse = response
+        .json()
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+    Ok(data.data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FollowedChannel {
+    pub broadcaster_id: String,
+    pub broadcaster_login: String,
+    pub broadcaster_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Pagination {
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FollowedChannelsResponse {
+    pub data: Vec<FollowedChannel>,
+    pub pagination: Pagination,
+}
+
+pub async fn get_followed_channels(
     access_token: &str,
     user_id: &str,
     client_id: &str,
-) -> Result<Status, Status> {
+    after: Option<&str>,
+) -> Result<FollowedChannelsResponse, Status> {
+    let cursor = after.map(|a| format!("&after={}", a)).unwrap_or_default();
+
     let request = Request::builder()
         .uri(format!(
-            "https://api.twitch.tv/helix/streams/tags?broadcaster_id={}",
-            user_id
+            "https://api.twitch.tv/helix/channels/followed?user_id={}&first=100{}",
+            user_id, cursor
         ))
-        .method("PUT")
+        .method("GET")
         .header("Authorization", access_token)
         .header("Client-Id", client_id)
-        .header("Content-Type", "application/json")
         .body(())
         .unwrap();
 
-    let response = isahc::send_async(request).await.unwrap();
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
 
     if response.status() != StatusCode::OK {
-        info!("user not found failed with {:?}", response.status());
-        return Err(Status::NotFound);
+        info!("followed channels failed with {:?}", response.status());
+        return Err(Status::BadGateway);
     }
-    Ok(Status::NoContent)
+    response.json().await.map_err(|_| Status::BadGateway)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwitchCategory {
+    pub id: String,
+    pub name: String,
+    pub box_art_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchCategoriesResponse {
+    data: Vec<TwitchCategory>,
 }
 
-pub async fn replace_stream_tags(
+pub async fn search_categories(
     access_token: &str,
-    user_id: &str,
     client_id: &str,
-    request: ReplaceTagsRequest,
-) -> Result<Status, Status> {
+    query: &str,
+) -> Result<Vec<TwitchCategory>, Status> {
     let request = Request::builder()
         .uri(format!(
-            "https://api.twitch.tv/helix/streams/tags?broadcaster_id={}",
-            user_id
+            "https://api.twitch.tv/helix/search/categories?first=20&query={}",
+            RawStr::new(query).percent_encode()
         ))
-        .method("PUT")
+        .method("GET")
         .header("Authorization", access_token)
         .header("Client-Id", client_id)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_string(&request).unwrap())
+        .body(())
         .unwrap();
 
-    let response = isahc::send_async(request).await.unwrap();
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
 
     if response.status() != StatusCode::OK {
-        info!("user not found failed with {:?}", response.status());
-        return Err(Status::NotFound);
+        info!("category search failed with {:?}", response.status());
+        return Err(Status::BadGateway);
+    }
+    let data: SearchCategoriesResponse = response.json().await.map_err(|_| Status::BadGateway)?;
+    Ok(data.data)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopGamesResponse {
+    pub data: Vec<TwitchCategory>,
+    pub pagination: Pagination,
+}
+
+/// One page of the most watched categories, Twitch doesn't offer a listing of every category.
+pub async fn get_top_games(
+    access_token: &str,
+    client_id: &str,
+    after: Option<&str>,
+) -> Result<TopGamesResponse, Status> {
+    let cursor = after.map(|a| format!("&after={}", a)).unwrap_or_default();
+
+    let request = Request::builder()
+        .uri(format!(
+            "https://api.twitch.tv/helix/games/top?first=100{}",
+            cursor
+        ))
+        .method("GET")
+        .header("Authorization", access_token)
+        .header("Client-Id", client_id)
+        .body(())
+        .unwrap();
+
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    if response.status() != StatusCode::OK {
+        info!("top games failed with {:?}", response.status());
+        return Err(Status::BadGateway);
+    }
+    response.json().await.map_err(|_| Status::BadGateway)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwitchUserToken {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+async fn request_user_token(query: String) -> Result<TwitchUserToken, Status> {
+    let request = Request::builder()
+        .uri(format!("https://id.twitch.tv/oauth2/token?{}", query))
+        .method("POST")
+        .body(())
+        .unwrap();
+
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    match response.status() {
+        StatusCode::OK => response.json().await.map_err(|_| Status::BadGateway),
+        // Twitch answers a spent or revoked code or refresh token with 400
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => {
+            info!("user token request rejected with {:?}", response.status());
+            Err(Status::Unauthorized)
+        }
+        status => {
+            info!("user token request failed with {:?}", status);
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+pub async fn exchange_authorization_code(
+    code: &str,
+    client_id: &str,
+    client_secret: &str,
+    redirect_uri: &str,
+) -> Result<TwitchUserToken, Status> {
+    request_user_token(format!(
+        "client_id={}&client_secret={}&code={}&grant_type=authorization_code&redirect_uri={}",
+        client_id,
+        client_secret,
+        RawStr::new(code).percent_encode(),
+        RawStr::new(redirect_uri).percent_encode()
+    ))
+    .await
+}
+
+/// Twitch may rotate the refresh token, so the one returned here replaces the stored one.
+pub async fn refresh_user_token(
+    refresh_token: &str,
+    client_id: &str,
+    client_secret: &str,
+) -> Result<TwitchUserToken, Status> {
+    request_user_token(format!(
+        "client_id={}&client_secret={}&grant_type=refresh_token&refresh_token={}",
+        client_id,
+        client_secret,
+        RawStr::new(refresh_token).percent_encode()
+    ))
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeListResponse<T> {
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+}
+
+async fn get_youtube_list<T: serde::de::DeserializeOwned + Unpin>(
+    access_token: &str,
+    path_and_query: &str,
+) -> Result<Vec<T>, Status> {
+    let request = Request::builder()
+        .uri(format!(
+            "https://www.googleapis.com/youtube/v3/{}",
+            path_and_query
+        ))
+        .method("GET")
+        .header("Authorization", access_token)
+        .body(())
+        .unwrap();
+
+    let mut response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    if response.status() != StatusCode::OK {
+        info!("youtube lookup failed with {:?}", response.status());
+        return Err(Status::from_code(response.status().as_u16()).unwrap_or(Status::BadGateway));
+    }
+    let data: YoutubeListResponse<T> = response.json().await.map_err(|_| Status::BadGateway)?;
+    Ok(data.items)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeChannel {
+    pub id: String,
+}
+
+pub async fn get_youtube_channel(access_token: &str) -> Result<YoutubeChannel, Status> {
+    get_youtube_list(access_token, "channels?part=id&mine=true")
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(Status::NotFound)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeBroadcast {
+    pub id: String,
+}
+
+/// The broadcast that is live right now, otherwise the next upcoming one.
+pub async fn get_youtube_broadcast(access_token: &str) -> Result<YoutubeBroadcast, Status> {
+    for broadcast_status in &["active", "upcoming"] {
+        let broadcasts = get_youtube_list(
+            access_token,
+            &format!(
+                "liveBroadcasts?part=id&broadcastType=all&maxResults=1&broadcastStatus={}",
+                broadcast_status
+            ),
+        )
+        .await?;
+
+        if let Some(broadcast) = broadcasts.into_iter().next() {
+            return Ok(broadcast);
+        }
+    }
+
+    Err(Status::NotFound)
+}
+
+/// Writing a snippet replaces it whole, so every writable field we know of is carried through.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeVideoSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub category_id: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_audio_language: Option<String>,
+}
+
+/// A live broadcast shares its id with the video that carries its title and category.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct YoutubeVideo {
+    pub id: String,
+    pub snippet: YoutubeVideoSnippet,
+}
+
+pub async fn get_youtube_video(access_token: &str, id: &str) -> Result<YoutubeVideo, Status> {
+    get_youtube_list(
+        access_token,
+        &format!(
+            "videos?part=snippet&id={}",
+            RawStr::new(id).percent_encode()
+        ),
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or(Status::NotFound)
+}
+
+pub async fn update_youtube_video(
+    access_token: &str,
+    video: &YoutubeVideo,
+) -> Result<Status, Status> {
+    let request = Request::builder()
+        .uri("https://www.googleapis.com/youtube/v3/videos?part=snippet")
+        .method("PUT")
+        .header("Authorization", access_token)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(video).map_err(|_| Status::InternalServerError)?)
+        .unwrap();
+
+    let response = isahc::send_async(request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    if !response.status().is_success() {
+        info!("youtube rejected change with {:?}", response.status());
+        return Err(Status::from_code(response.status().as_u16()).unwrap_or(Status::BadGateway));
     }
     Ok(Status::NoContent)
 }
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeCategorySnippet {
+    pub title: String,
+    #[serde(default)]
+    pub assignable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeCategory {
+    pub id: String,
+    pub snippet: YoutubeCategorySnippet,
+}
+
+/// `filter` is either `id=...` or `regionCode=...`, as the categories API requires one of them.
+pub async fn get_youtube_categories(
+    access_token: &str,
+    filter: &str,
+) -> Result<Vec<YoutubeCategory>, Status> {
+    get_youtube_list(
+        access_token,
+        &format!("videoCategories?part=snippet&{}", filter),
+    )
+    .await
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use rocket::{
+    error, fairing::AdHoc, get, http::Status, info, serde::json::Json, tokio, warn, State,
+};
+use serde::Serialize;
+
+use crate::{
+    authenticate::PlatformAccessToken,
+    database::{
+        favourite_streams::StreamSource,
+        stream_catalogue::{
+            find_stream_catalogue, find_stream_catalogue_ages, replace_stream_catalogue,
+            StreamCatalogueModel,
+        },
+        worker_database,
+    },
+    service::get_app_access_token,
+    stream_platform::{stream_platform, StreamCategory, StreamPlatform, TwitchPlatform},
+    DbConn, GlobalConfig,
+};
+
+const CATALOGUE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const CATALOGUE_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_LOCALE: &str = "en";
+const MAX_LOCALE_LENGTH: usize = 10;
+
+/// `localised` is false when the platform only names categories in one language, whatever the
+/// requested locale.
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogueTag {
+    pub id: String,
+    pub tag_name: String,
+    pub localised: bool,
+}
+
+impl CatalogueTag {
+    fn from(category: &StreamCategory, platform: &dyn StreamPlatform) -> Self {
+        Self {
+            id: category.id.clone(),
+            tag_name: category.name.clone(),
+            localised: platform.localised(),
+        }
+    }
+}
+
+type CachedCatalogue = (Instant, Arc<Vec<StreamCategory>>);
+
+/// Catalogues by provider and locale, each kept until it expires, held in managed state and
+/// shared with the background refresh.
+#[derive(Clone, Default)]
+pub struct StreamCatalogueCache {
+    catalogues: Arc<Mutex<HashMap<(StreamSource, String), CachedCatalogue>>>,
+}
+
+impl StreamCatalogueCache {
+    fn cached(&self, provider: StreamSource, locale: &str) -> Option<Arc<Vec<StreamCategory>>> {
+        match self
+            .catalogues
+            .lock()
+            .unwrap()
+            .get(&(provider, locale.to_owned()))
+        {
+            Some((expires_at, catalogue)) if Instant::now() < *expires_at => {
+                Some(catalogue.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn store(
+        &self,
+        provider: StreamSource,
+        locale: &str,
+        expires_at: Instant,
+        catalogue: Vec<StreamCategory>,
+    ) -> Arc<Vec<StreamCategory>> {
+        let catalogue = Arc::new(catalogue);
+        self.catalogues.lock().unwrap().insert(
+            (provider, locale.to_owned()),
+            (expires_at, catalogue.clone()),
+        );
+        catalogue
+    }
+}
+
+/// Lowercases a requested locale such as `de` or `pt-BR`, rejecting anything that isn't one.
+fn parse_locale(locale: Option<&str>) -> Result<String, Status> {
+    let locale = match locale {
+        Some(locale) => locale.to_lowercase(),
+        None => return Ok(DEFAULT_LOCALE.to_owned()),
+    };
+
+    let valid = locale.len() >= 2
+        && locale.len() <= MAX_LOCALE_LENGTH
+        && locale
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(locale),
+        false => Err(Status::UnprocessableEntity),
+    }
+}
+
+async fn refresh_catalogue(
+    db_conn: &DbConn,
+    cache: &StreamCatalogueCache,
+    platform: &dyn StreamPlatform,
+    access_token: &str,
+    locale: &str,
+) -> Result<Arc<Vec<StreamCategory>>, Status> {
+    let categories = platform.list_categories(access_token, locale).await?;
+    info!(
+        "fetched {} {:?} categories for {}",
+        categories.len(),
+        platform.source(),
+        locale
+    );
+
+    let entries = categories
+        .iter()
+        .map(|c| StreamCatalogueModel {
+            provider: platform.source(),
+            locale: locale.to_owned(),
+            id: c.id.clone(),
+            name: c.name.clone(),
+            box_art_url: c.box_art_url.clone(),
+        })
+        .collect();
+    replace_stream_catalogue(db_conn, platform.source(), locale.to_owned(), entries).await?;
+
+    Ok(cache.store(
+        platform.source(),
+        locale,
+        Instant::now() + CATALOGUE_TTL,
+        categories,
+    ))
+}
+
+/// Serves the catalogue from memory, then Postgres, and only asks the platform once both expired.
+/// An expired catalogue is still served when the platform can't be reached.
+async fn get_catalogue(
+    db_conn: &DbConn,
+    cache: &StreamCatalogueCache,
+    platform: &dyn StreamPlatform,
+    access_token: &str,
+    locale: &str,
+) -> Result<Arc<Vec<StreamCategory>>, Status> {
+    if let Some(catalogue) = cache.cached(platform.source(), locale) {
+        return Ok(catalogue);
+    }
+
+    let saved = find_stream_catalogue(db_conn, platform.source(), locale.to_owned()).await?;
+    let oldest = saved.iter().map(|e| e.fetched_at).min();
+    let saved: Vec<StreamCategory> = saved
+        .into_iter()
+        .map(|e| StreamCategory {
+            id: e.id,
+            name: e.name,
+            box_art_url: e.box_art_url,
+        })
+        .collect();
+
+    if let Some(fetched_at) = oldest {
+        let age = (Utc::now() - fetched_at).to_std().unwrap_or_default();
+        if age < CATALOGUE_TTL {
+            let expires_at = Instant::now() + (CATALOGUE_TTL - age);
+            return Ok(cache.store(platform.source(), locale, expires_at, saved));
+        }
+    }
+
+    match refresh_catalogue(db_conn, cache, platform, access_token, locale).await {
+        Ok(catalogue) => Ok(catalogue),
+        Err(status) if !saved.is_empty() => {
+            warn!(
+                "serving expired {:?} catalogue for {}, refresh failed: {}",
+                platform.source(),
+                locale,
+                status
+            );
+            Ok(Arc::new(saved))
+        }
+        Err(status) => Err(status),
+    }
+}
+
+#[get("/stream-management/tags/<provider>?<prefix>&<locale>")]
+pub async fn get_stream_tags(
+    db_conn: DbConn,
+    platform_token: PlatformAccessToken,
+    provider: &str,
+    prefix: Option<&str>,
+    locale: Option<&str>,
+    global_config: &State<GlobalConfig>,
+    cache: &State<StreamCatalogueCache>,
+) -> Result<Json<Vec<CatalogueTag>>, Status> {
+    let platform = stream_platform(Some(provider), global_config)?;
+    let locale = match platform.localised() {
+        true => parse_locale(locale)?,
+        false => DEFAULT_LOCALE.to_owned(),
+    };
+    let prefix = prefix.unwrap_or_default().trim().to_lowercase();
+
+    let catalogue = get_catalogue(
+        &db_conn,
+        cache,
+        platform.as_ref(),
+        &platform_token.0,
+        &locale,
+    )
+    .await?;
+
+    let mut tags: Vec<CatalogueTag> = catalogue
+        .iter()
+        .filter(|c| c.name.to_lowercase().starts_with(&prefix))
+        .map(|c| CatalogueTag::from(c, platform.as_ref()))
+        .collect();
+
+    if tags.is_empty() && !prefix.is_empty() && !platform.complete_catalogue() {
+        tags = platform
+            .search_categories(&platform_token.0, &prefix)
+            .await?
+            .iter()
+            .map(|c| CatalogueTag::from(c, platform.as_ref()))
+            .collect();
+    }
+
+    Ok(Json(tags))
+}
+
+/// Refreshes Twitch catalogues before they expire, YouTube has no app credentials so its
+/// catalogues are refreshed by the first request after they expire instead.
+async fn refresh_expiring_catalogues(
+    db_conn: &DbConn,
+    cache: &StreamCatalogueCache,
+    global_config: &GlobalConfig,
+) -> Result<(), Status> {
+    let ages = find_stream_catalogue_ages(db_conn).await?;
+    let twitch_fresh = ages.iter().any(|(provider, _, fetched_at)| {
+        let age = (Utc::now() - *fetched_at).to_std().unwrap_or_default();
+        *provider == StreamSource::Twitch && age + 2 * CATALOGUE_REFRESH_INTERVAL < CATALOGUE_TTL
+    });
+    if twitch_fresh {
+        return Ok(());
+    }
+
+    let app_token = get_app_access_token(
+        &global_config.twitch_client_id,
+        &global_config.twitch_client_secret,
+    )
+    .await?;
+    let platform = TwitchPlatform {
+        client_id: global_config.twitch_client_id.clone(),
+    };
+
+    refresh_catalogue(
+        db_conn,
+        cache,
+        &platform,
+        &format!("Bearer {}", app_token.access_token),
+        DEFAULT_LOCALE,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Keeps the catalogue warm in the background for as long as the server is up.
+pub fn stream_catalogue_worker() -> AdHoc {
+    AdHoc::on_liftoff("Stream catalogue worker", |rocket| {
+        Box::pin(async move {
+            let database = match worker_database(rocket).await {
+                Some(database) => database,
+                None => return,
+            };
+            let (global_config, cache) = match (
+                rocket.state::<GlobalConfig>(),
+                rocket.state::<StreamCatalogueCache>(),
+            ) {
+                (Some(global_config), Some(cache)) => (global_config.clone(), cache.clone()),
+                _ => return,
+            };
+            let mut shutdown = rocket.shutdown();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(CATALOGUE_REFRESH_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            let db_conn = match DbConn::get_one(&database).await {
+                                Some(db_conn) => db_conn,
+                                None => continue,
+                            };
+                            if let Err(status) = refresh_expiring_catalogues(&db_conn, &cache, &global_config).await {
+                                error!("refreshing the stream catalogue failed: {}", status);
+                            }
+                        }
+                        _ = &mut shutdown => break,
+                    }
+                }
+            });
+        })
+    })
+}
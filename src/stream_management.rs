@@ -1,105 +1,316 @@
-use futures::future::{join, try_join_all};
-use rocket::{debug, get, http::Status, post, put, serde::json::Json, State};
+use std::collections::HashMap;
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use rocket::{debug, delete, get, http::Status, post, put, serde::json::Json, Responder, State};
 use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors};
 
 use crate::{
-    authenticate::AccessToken,
+    authenticate::{AccessToken, PlatformAccessToken, StreamIdentity},
+    database::favourite_streams::StreamSource,
+    database::linked_account::find_linked_profile,
+    database::preset_application::{
+        insert_preset_application, PresetApplicationModel, SavedPresetApplicationModel,
+    },
+    database::preset_counter::{find_preset_counters, increment_preset_counters},
     database::stream_management::{
-        find_stream_tag, find_stream_title, find_stream_titles, insert_stream_tag,
-        insert_stream_title, SavedTagModel, StreamTagModel, StreamTitleModel,
+        delete_stream_preset, find_stream_tag, find_stream_title, find_stream_titles,
+        insert_stream_preset, update_stream_preset, SavedTagModel, SavedTitleModel,
+        StreamTitleModel,
     },
-    service::{
-        get_channel_information, get_twitch_profile, modify_channel_information,
-        replace_stream_tags, ModifyChannelRequest, ReplaceTagsRequest, TwitchUser,
+    service::{get_twitch_profile, ModifyChannelRequest, TwitchChannelInformation, TwitchUser},
+    stream_platform::{stream_platform, StreamCategory, StreamPlatform},
+    title_template::{
+        is_valid_counter_name, local_date, parse_timezone, TemplateValues, TitleTemplate,
+        MAX_COUNTER_VALUE,
     },
     DbConn, GlobalConfig,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct StreamManagementRequest {
+    #[validate]
     pub title: StreamTitle,
+    /// Checked against the platform's own tag rules, see `StreamPlatform::fit_tags`.
     pub tags: Vec<StreamTag>,
+    #[serde(default)]
+    #[validate]
+    pub game: Option<StreamGame>,
+    #[serde(default)]
+    #[validate(length(min = 2, max = 5))]
+    pub broadcaster_language: Option<String>,
+    /// Only used by platforms that have one, such as YouTube.
+    #[serde(default)]
+    #[validate(length(max = 5000))]
+    pub description: Option<String>,
+    /// Where the title's counters stand, the next apply shows each one plus one.
+    #[serde(default)]
+    pub counters: HashMap<String, i32>,
+    /// IANA timezone the title's `{date}` and `{weekday}` are rendered in, UTC when unset.
+    #[serde(default)]
+    #[validate(custom = "validate_timezone")]
+    pub timezone: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A Twitch category, `id` is what gets sent to Twitch and `name` is kept for display.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+pub struct StreamGame {
+    #[validate(length(min = 1))]
+    pub id: String,
+    #[validate(length(min = 1))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct StreamTitle {
+    #[validate(length(max = 140), custom = "validate_not_blank")]
     pub title: String,
 }
 
+/// `id` is only set on tags saved against Twitch's retired tag_id API, new tags are freeform.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StreamTag {
-    pub id: String,
+    #[serde(default)]
+    pub id: Option<String>,
     pub name: String,
 }
 
+fn validate_not_blank(value: &str) -> Result<(), ValidationError> {
+    match value.trim().is_empty() {
+        true => Err(ValidationError::new("blank")),
+        false => Ok(()),
+    }
+}
+
+fn validate_timezone(value: &str) -> Result<(), ValidationError> {
+    match parse_timezone(Some(value)) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("timezone")),
+    }
+}
+
+#[derive(Debug, Responder)]
+pub enum StreamManagementError {
+    #[response(status = 422)]
+    Validation(Json<ValidationErrors>),
+    Status(Status),
+}
+
+impl From<Status> for StreamManagementError {
+    fn from(status: Status) -> Self {
+        StreamManagementError::Status(status)
+    }
+}
+
+impl From<ValidationErrors> for StreamManagementError {
+    fn from(errors: ValidationErrors) -> Self {
+        StreamManagementError::Validation(Json(errors))
+    }
+}
+
+/// Saved tags as the platform takes them, tags saved under older rules are made to fit.
+pub fn freeform_tags(platform: &dyn StreamPlatform, tags: Vec<SavedTagModel>) -> Vec<String> {
+    platform.fit_tags(tags.into_iter().map(|t| t.name).collect())
+}
+
 pub fn get_access_token(token: &str) -> String {
     token.replace("Bearer ", "OAuth ")
 }
 
-pub async fn get_user(access_token: &AccessToken) -> Result<TwitchUser, Status> {
-    let parsed_token = get_access_token(&access_token.0);
+pub async fn get_user(platform_token: &PlatformAccessToken) -> Result<TwitchUser, Status> {
+    let parsed_token = get_access_token(&platform_token.0);
     get_twitch_profile(&parsed_token).await
 }
 
-#[post("/stream-management", data = "<stream_management_request>")]
-pub async fn post_stream_management(
-    stream_management_request: Json<StreamManagementRequest>,
-    db_conn: DbConn,
-    access_token: AccessToken,
-) -> Result<Status, Status> {
-    let profile: TwitchUser = get_user(&access_token).await?;
+/// Who a preset change is for, their account on the platform and the BeemStream profile owning
+/// their presets. Accounts not linked to a profile yet have none, see `linked_accounts`.
+#[derive(Debug, Clone)]
+pub struct StreamUser {
+    pub profile_id: Option<i32>,
+    pub user_id: String,
+}
 
-    let stream_management_inner = stream_management_request.into_inner();
+/// Resolves the platform a route was called for and who the platform token belongs to there. A
+/// caller naming their profile must have linked that account to it first, `PreconditionRequired`
+/// when it isn't linked and `Forbidden` when it is linked to someone else.
+pub async fn get_platform_user(
+    db_conn: &DbConn,
+    identity: &StreamIdentity,
+    provider: Option<&str>,
+    global_config: &GlobalConfig,
+) -> Result<(Box<dyn StreamPlatform>, StreamUser), Status> {
+    let platform = stream_platform(provider, global_config)?;
+    let user_id = platform.get_user_id(&identity.platform_token.0).await?;
+    let linked_profile = find_linked_profile(db_conn, platform.source(), user_id.clone()).await?;
 
-    let stream_title_model =
-        StreamTitleModel::from(&stream_management_inner.title, profile.user_id);
+    let profile_id = match (identity.profile_id, linked_profile) {
+        (Some(profile_id), Some(linked)) if profile_id != linked => return Err(Status::Forbidden),
+        (Some(_), None) => return Err(Status::PreconditionRequired),
+        (_, linked) => linked,
+    };
+
+    Ok((
+        platform,
+        StreamUser {
+            profile_id,
+            user_id,
+        },
+    ))
+}
 
-    let stream_title = insert_stream_title(&db_conn, stream_title_model).await?;
+/// Limits that differ between platforms, checked on top of the request's own validation.
+fn validate_for_platform(
+    stream_management_request: &StreamManagementRequest,
+    platform: &dyn StreamPlatform,
+) -> Result<(), ValidationErrors> {
+    stream_management_request.validate()?;
 
-    let save_tags = stream_management_inner
+    let mut errors = ValidationErrors::new();
+    let title = &stream_management_request.title.title;
+    if title.chars().count() > platform.max_title_length() {
+        errors.add("title", ValidationError::new("length"));
+    }
+    if TitleTemplate::parse(title).is_none() {
+        errors.add("title", ValidationError::new("template"));
+    }
+    let tags: Vec<String> = stream_management_request
         .tags
-        .into_iter()
-        .map(|t| StreamTagModel::from(&t, stream_title.id))
-        .collect::<Vec<StreamTagModel>>();
+        .iter()
+        .map(|t| t.name.clone())
+        .collect();
+    if platform.fit_tags(tags.clone()) != tags {
+        errors.add("tags", ValidationError::new("tags"));
+    }
+    validate_counters(&stream_management_request.counters, &mut errors);
 
-    debug!("saving tags {:?}", save_tags);
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
 
-    let futures: Vec<_> = save_tags
-        .into_iter()
-        .map(|t| insert_stream_tag(&db_conn, t))
-        .collect();
+fn validate_counters(counters: &HashMap<String, i32>, errors: &mut ValidationErrors) {
+    if counters.iter().any(|(name, value)| {
+        !is_valid_counter_name(name) || !(0..=MAX_COUNTER_VALUE).contains(value)
+    }) {
+        errors.add("counters", ValidationError::new("counter"));
+    }
+}
+
+async fn save_stream_preset(
+    stream_management_request: StreamManagementRequest,
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    provider: Option<&str>,
+    global_config: &GlobalConfig,
+) -> Result<Status, StreamManagementError> {
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
 
-    try_join_all(futures).await?;
+    validate_for_platform(&stream_management_request, platform.as_ref())?;
+
+    let stream_title_model =
+        StreamTitleModel::from(&stream_management_request, user, platform.source());
+
+    debug!("saving tags {:?}", stream_management_request.tags);
+
+    insert_stream_preset(
+        &db_conn,
+        stream_title_model,
+        stream_management_request.tags,
+        stream_management_request.counters,
+    )
+    .await?;
 
-    // let stream_tag_model = StreamTagModel::from(&stream_management_request.tags, );
     Ok(Status::Ok)
 }
 
+#[post("/stream-management?<provider>", data = "<stream_management_request>")]
+pub async fn post_stream_management(
+    stream_management_request: Json<StreamManagementRequest>,
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, StreamManagementError> {
+    save_stream_preset(
+        stream_management_request.into_inner(),
+        db_conn,
+        identity,
+        provider,
+        global_config,
+    )
+    .await
+}
+
+#[post(
+    "/stream-management/stream-preset/<provider>",
+    data = "<stream_management_request>"
+)]
+pub async fn post_stream_preset(
+    stream_management_request: Json<StreamManagementRequest>,
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    provider: &str,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, StreamManagementError> {
+    save_stream_preset(
+        stream_management_request.into_inner(),
+        db_conn,
+        identity,
+        Some(provider),
+        global_config,
+    )
+    .await
+}
+
 #[derive(Debug, Serialize)]
 pub struct StreamPreset {
     pub id: i32,
     pub title: String,
     pub tags: Vec<SavedTagModel>,
+    pub game: Option<StreamGame>,
+    pub broadcaster_language: Option<String>,
+    pub description: Option<String>,
+    pub provider: StreamSource,
+    pub counters: HashMap<String, i32>,
+    pub timezone: Option<String>,
 }
 
-#[get("/stream-management")]
+#[get("/stream-management?<provider>")]
 pub async fn get_stream_management(
     db_conn: DbConn,
-    access_token: AccessToken,
+    identity: StreamIdentity,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
 ) -> Result<Json<Vec<StreamPreset>>, Status> {
     debug!("ran through");
-    let profile: TwitchUser = get_user(&access_token).await?;
-    let titles = find_stream_titles(&db_conn, profile.user_id).await?;
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+    let titles = find_stream_titles(&db_conn, user, platform.source()).await?;
 
     let mut stream_preset_response = vec![];
 
     for title in &titles {
         let tags = find_stream_tag(&db_conn, title.clone()).await?;
 
+        let game = match (&title.game_id, &title.game_name) {
+            (Some(id), Some(name)) => Some(StreamGame {
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            _ => None,
+        };
+
         let response = StreamPreset {
             id: title.id,
             title: title.title.clone(),
             tags,
+            game,
+            broadcaster_language: title.broadcaster_language.clone(),
+            description: title.description.clone(),
+            provider: title.provider,
+            counters: find_preset_counters(&db_conn, title.id).await?,
+            timezone: title.timezone.clone(),
         };
 
         stream_preset_response.push(response);
@@ -108,48 +319,443 @@ pub async fn get_stream_management(
     Ok(Json(stream_preset_response))
 }
 
-#[put("/stream-management/<preset_id>/set")]
-pub async fn put_stream_management(
+#[put(
+    "/stream-management/<preset_id>?<provider>",
+    data = "<stream_management_request>"
+)]
+pub async fn update_stream_management(
+    stream_management_request: Json<StreamManagementRequest>,
     db_conn: DbConn,
-    access_token: AccessToken,
+    identity: StreamIdentity,
     preset_id: i32,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, StreamManagementError> {
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+
+    let stream_management_inner = stream_management_request.into_inner();
+    validate_for_platform(&stream_management_inner, platform.as_ref())?;
+
+    let stream_title_model =
+        StreamTitleModel::from(&stream_management_inner, user, platform.source());
+
+    update_stream_preset(
+        &db_conn,
+        preset_id,
+        stream_title_model,
+        stream_management_inner.tags,
+        stream_management_inner.counters,
+    )
+    .await?;
+
+    Ok(Status::NoContent)
+}
+
+#[delete("/stream-management/<preset_id>?<provider>")]
+pub async fn delete_stream_management(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    preset_id: i32,
+    provider: Option<&str>,
     global_config: &State<GlobalConfig>,
 ) -> Result<Status, Status> {
-    let profile: TwitchUser = get_user(&access_token).await?;
-    let title = find_stream_title(&db_conn, preset_id).await?;
-    let tags = find_stream_tag(&db_conn, title.clone()).await?;
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+
+    delete_stream_preset(&db_conn, preset_id, user, platform.source()).await?;
+
+    Ok(Status::NoContent)
+}
+
+/// The parts of a channel a preset can change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelState {
+    pub title: String,
+    pub tags: Vec<String>,
+    pub game: Option<StreamGame>,
+    pub broadcaster_language: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
 
-    let channel_info = get_channel_information(
-        &access_token.0,
-        &profile.user_id,
-        &global_config.twitch_client_id,
+impl ChannelState {
+    pub fn from(channel_info: &TwitchChannelInformation) -> Self {
+        // Twitch reports an unset category as an empty id rather than null
+        let game = match (&channel_info.game_id, &channel_info.game_name) {
+            (Some(id), Some(name)) if !id.is_empty() => Some(StreamGame {
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            _ => None,
+        };
+
+        Self {
+            title: channel_info.title.clone(),
+            tags: channel_info.tags.clone(),
+            game,
+            broadcaster_language: channel_info.broadcaster_language.clone(),
+            description: None,
+        }
+    }
+
+    /// Anything the preset leaves unset keeps the channel's current value.
+    pub fn with_preset(
+        &self,
+        platform: &dyn StreamPlatform,
+        title: SavedTitleModel,
+        tags: Vec<SavedTagModel>,
+    ) -> Self {
+        let game = match (title.game_id, title.game_name) {
+            (Some(id), Some(name)) => Some(StreamGame { id, name }),
+            _ => self.game.clone(),
+        };
+
+        Self {
+            title: title.title,
+            tags: freeform_tags(platform, tags),
+            game,
+            broadcaster_language: title
+                .broadcaster_language
+                .or_else(|| self.broadcaster_language.clone()),
+            description: title.description.or_else(|| self.description.clone()),
+        }
+    }
+
+    /// Twitch leaves the category alone for a null `game_id` and only clears it for an empty one.
+    pub fn into_modify_request(self) -> ModifyChannelRequest {
+        ModifyChannelRequest {
+            game_id: Some(self.game.map(|g| g.id).unwrap_or_default()),
+            broadcaster_language: self.broadcaster_language,
+            title: self.title,
+            tags: Some(self.tags),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ApplyOutcome {
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform_status: Option<u16>,
+}
+
+impl ApplyOutcome {
+    pub fn from(result: Result<Status, Status>) -> Self {
+        match result {
+            Ok(_) => Self {
+                applied: true,
+                platform_status: None,
+            },
+            Err(status) => Self {
+                applied: false,
+                platform_status: Some(status.code),
+            },
+        }
+    }
+}
+
+/// What caused a change to be pushed to the channel.
+#[derive(Debug, Clone, Copy)]
+pub enum ApplySource {
+    Preset(i32),
+    Revert(i32),
+}
+
+/// Pushes `applied_state` to the platform and records it in the apply history whatever the outcome.
+pub async fn apply_channel_state(
+    db_conn: &DbConn,
+    platform: &dyn StreamPlatform,
+    access_token: &str,
+    user: &StreamUser,
+    previous_state: ChannelState,
+    applied_state: ChannelState,
+    source: ApplySource,
+) -> Result<(ApplyOutcome, SavedPresetApplicationModel), Status> {
+    let result = platform
+        .modify_channel(access_token, &user.user_id, &applied_state)
+        .await;
+    let outcome = ApplyOutcome::from(result);
+
+    let (associated_title, reverted_application) = match source {
+        ApplySource::Preset(preset_id) => (Some(preset_id), None),
+        ApplySource::Revert(application_id) => (None, Some(application_id)),
+    };
+
+    let application = PresetApplicationModel {
+        associated_user: user.user_id.clone(),
+        associated_title,
+        reverted_application,
+        previous_state: serde_json::to_value(previous_state)
+            .map_err(|_| Status::InternalServerError)?,
+        applied_state: serde_json::to_value(applied_state)
+            .map_err(|_| Status::InternalServerError)?,
+        applied: outcome.applied,
+        platform_status: outcome.platform_status.map(i32::from),
+        provider: platform.source(),
+        associated_profile: user.profile_id,
+    };
+    let saved = insert_preset_application(db_conn, application).await?;
+
+    Ok((outcome, saved))
+}
+
+/// Fills in the preset's title template as of now in its timezone, with every counter it uses one
+/// ahead. Titles saved before templates existed may not parse and are used as they are.
+async fn render_preset_title(
+    db_conn: &DbConn,
+    preset_id: i32,
+    timezone: Option<&str>,
+    state: &ChannelState,
+) -> Result<(String, Vec<String>), Status> {
+    let template = match TitleTemplate::parse(&state.title) {
+        Some(template) => template,
+        None => return Ok((state.title.clone(), vec![])),
+    };
+    let used = template.counters();
+
+    let mut next = HashMap::new();
+    if !used.is_empty() {
+        let counters = find_preset_counters(db_conn, preset_id).await?;
+        for name in &used {
+            next.insert(
+                name.clone(),
+                counters
+                    .get(name)
+                    .copied()
+                    .unwrap_or_default()
+                    .saturating_add(1)
+                    .min(MAX_COUNTER_VALUE),
+            );
+        }
+    }
+
+    let timezone = parse_timezone(timezone).unwrap_or(Tz::UTC);
+    let title = template.render(&TemplateValues {
+        date: local_date(Utc::now(), timezone),
+        game: state.game.as_ref().map(|g| g.name.as_str()),
+        counters: &next,
+    });
+
+    Ok((title, used))
+}
+
+/// Applies one of the user's presets on top of their current channel, shared by every way of applying one.
+pub async fn apply_stream_preset(
+    db_conn: &DbConn,
+    platform: &dyn StreamPlatform,
+    access_token: &str,
+    user: &StreamUser,
+    preset_id: i32,
+) -> Result<(ApplyOutcome, SavedPresetApplicationModel), Status> {
+    let title = find_stream_title(db_conn, preset_id, user.clone(), platform.source()).await?;
+    let tags = find_stream_tag(db_conn, title.clone()).await?;
+
+    let previous_state = platform.get_channel(access_token, &user.user_id).await?;
+    let timezone = title.timezone.clone();
+    let mut applied_state = previous_state.with_preset(platform, title, tags);
+    let (rendered, counters) =
+        render_preset_title(db_conn, preset_id, timezone.as_deref(), &applied_state).await?;
+    if rendered.chars().count() > platform.max_title_length() {
+        return Err(Status::UnprocessableEntity);
+    }
+    applied_state.title = rendered;
+
+    let (outcome, application) = apply_channel_state(
+        db_conn,
+        platform,
+        access_token,
+        user,
+        previous_state,
+        applied_state,
+        ApplySource::Preset(preset_id),
     )
     .await?;
 
-    let channel_modify_request = ModifyChannelRequest {
-        game_id: channel_info.game_id.clone(),
-        broadcaster_language: channel_info.broadcaster_language.clone(),
-        title: title.title,
+    // counters only move once the title showing them is live
+    if outcome.applied && !counters.is_empty() {
+        increment_preset_counters(db_conn, preset_id, counters).await?;
+    }
+
+    Ok((outcome, application))
+}
+
+/// Title and tags share one platform update today, but are reported apart so clients don't depend on that.
+#[derive(Debug, Serialize)]
+pub struct ApplyPresetResponse {
+    pub title: ApplyOutcome,
+    pub tags: ApplyOutcome,
+}
+
+#[put("/stream-management/<preset_id>/set?<provider>")]
+pub async fn put_stream_management(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    preset_id: i32,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<(Status, Json<ApplyPresetResponse>), Status> {
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+
+    let (outcome, _) = apply_stream_preset(
+        &db_conn,
+        platform.as_ref(),
+        &identity.platform_token.0,
+        &user,
+        preset_id,
+    )
+    .await?;
+
+    let response = ApplyPresetResponse {
+        title: outcome,
+        tags: outcome,
     };
 
-    let tag_ids: Vec<String> = tags.into_iter().map(|t| t.source_id).collect();
+    let status = match (response.title.applied, response.tags.applied) {
+        (true, true) => Status::Ok,
+        _ => Status::BadGateway,
+    };
 
-    let replace_tags_request = ReplaceTagsRequest { tag_ids: vec![] };
+    Ok((status, Json(response)))
+}
 
-    let channel_modify = modify_channel_information(
-        &access_token.0,
-        &profile.user_id,
-        &global_config.twitch_client_id,
-        channel_modify_request,
-    );
-    let replace_tags = replace_stream_tags(
-        &access_token.0,
-        &profile.user_id,
-        &global_config.twitch_client_id,
-        replace_tags_request,
-    );
+#[get("/stream-management/categories?<query>&<provider>")]
+pub async fn get_stream_categories(
+    platform_token: PlatformAccessToken,
+    query: &str,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Json<Vec<StreamCategory>>, Status> {
+    let platform = stream_platform(provider, global_config)?;
+    if query.trim().is_empty() {
+        return Ok(Json(vec![]));
+    }
 
-    let (_, _) = join(channel_modify, replace_tags).await;
+    let categories = platform.search_categories(&platform_token.0, query).await?;
 
-    Ok(Status::NoContent)
+    Ok(Json(categories))
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldDiff<T> {
+    pub current: T,
+    pub preset: T,
+    pub changed: bool,
+}
+
+impl<T: PartialEq> FieldDiff<T> {
+    pub fn from(current: T, preset: T) -> Self {
+        let changed = current != preset;
+        Self {
+            current,
+            preset,
+            changed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PresetPreview {
+    pub title: FieldDiff<String>,
+    pub tags: FieldDiff<Vec<String>>,
+    pub game: FieldDiff<Option<StreamGame>>,
+    pub broadcaster_language: FieldDiff<Option<String>>,
+    pub description: FieldDiff<Option<String>>,
+}
+
+fn normalized_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().map(|t| t.to_lowercase()).collect();
+    tags.sort();
+    tags
+}
+
+// ranked after `/stream-management/tags/<provider>`, a preset id is never "tags"
+#[get("/stream-management/<preset_id>/preview?<provider>", rank = 2)]
+pub async fn get_stream_management_preview(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    preset_id: i32,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Json<PresetPreview>, Status> {
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+    let title = find_stream_title(&db_conn, preset_id, user.clone(), platform.source()).await?;
+    let tags = find_stream_tag(&db_conn, title.clone()).await?;
+
+    let current = platform
+        .get_channel(&identity.platform_token.0, &user.user_id)
+        .await?;
+    let timezone = title.timezone.clone();
+    let mut preset = current.with_preset(platform.as_ref(), title, tags);
+    preset.title = render_preset_title(&db_conn, preset_id, timezone.as_deref(), &preset)
+        .await?
+        .0;
+
+    // neither platform treats tags case-sensitively or keeps their order
+    let tags_changed = normalized_tags(&current.tags) != normalized_tags(&preset.tags);
+
+    Ok(Json(PresetPreview {
+        title: FieldDiff::from(current.title, preset.title),
+        tags: FieldDiff {
+            current: current.tags,
+            preset: preset.tags,
+            changed: tags_changed,
+        },
+        game: FieldDiff::from(current.game, preset.game),
+        broadcaster_language: FieldDiff::from(
+            current.broadcaster_language,
+            preset.broadcaster_language,
+        ),
+        description: FieldDiff::from(current.description, preset.description),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TitleRenderRequest {
+    pub title: String,
+    #[serde(default)]
+    pub game: Option<String>,
+    #[serde(default)]
+    pub counters: HashMap<String, i32>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TitleRenderResponse {
+    pub title: String,
+    pub counters: Vec<String>,
+}
+
+/// Renders a title template as applying it right now would, counters are taken as given.
+#[post("/stream-management/render", data = "<title_render_request>")]
+pub async fn post_title_render(
+    _access_token: AccessToken,
+    title_render_request: Json<TitleRenderRequest>,
+) -> Result<Json<TitleRenderResponse>, StreamManagementError> {
+    let request = title_render_request.into_inner();
+
+    let mut errors = ValidationErrors::new();
+    validate_counters(&request.counters, &mut errors);
+    let timezone = parse_timezone(request.timezone.as_deref());
+    if timezone.is_none() {
+        errors.add("timezone", ValidationError::new("timezone"));
+    }
+    let template = match TitleTemplate::parse(&request.title) {
+        Some(template) if errors.is_empty() => template,
+        Some(_) => return Err(errors.into()),
+        None => {
+            errors.add("title", ValidationError::new("template"));
+            return Err(errors.into());
+        }
+    };
+
+    let title = template.render(&TemplateValues {
+        date: local_date(Utc::now(), timezone.unwrap_or(Tz::UTC)),
+        game: request.game.as_deref(),
+        counters: &request.counters,
+    });
+
+    Ok(Json(TitleRenderResponse {
+        title,
+        counters: template.counters(),
+    }))
 }
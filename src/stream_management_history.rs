@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use rocket::{get, http::Status, post, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+    authenticate::StreamIdentity,
+    database::preset_application::{
+        find_preset_application, find_preset_applications, SavedPresetApplicationModel,
+    },
+    stream_management::{
+        apply_channel_state, get_platform_user, ApplyOutcome, ApplySource, ChannelState,
+    },
+    DbConn, GlobalConfig,
+};
+
+#[derive(Debug, Serialize)]
+pub struct PresetApplication {
+    pub id: i32,
+    pub preset_id: Option<i32>,
+    pub reverted_application: Option<i32>,
+    pub applied_at: DateTime<Utc>,
+    pub previous_state: ChannelState,
+    pub applied_state: ChannelState,
+    pub outcome: ApplyOutcome,
+}
+
+impl PresetApplication {
+    pub fn from(application: SavedPresetApplicationModel) -> Result<Self, Status> {
+        Ok(Self {
+            id: application.id,
+            preset_id: application.associated_title,
+            reverted_application: application.reverted_application,
+            applied_at: application.applied_at,
+            previous_state: serde_json::from_value(application.previous_state)
+                .map_err(|_| Status::InternalServerError)?,
+            applied_state: serde_json::from_value(application.applied_state)
+                .map_err(|_| Status::InternalServerError)?,
+            outcome: ApplyOutcome {
+                applied: application.applied,
+                platform_status: application.platform_status.map(|s| s as u16),
+            },
+        })
+    }
+}
+
+#[get("/stream-management/history?<provider>")]
+pub async fn get_stream_management_history(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Json<Vec<PresetApplication>>, Status> {
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+
+    let history = find_preset_applications(&db_conn, user, platform.source())
+        .await?
+        .into_iter()
+        .map(PresetApplication::from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Json(history))
+}
+
+/// Puts the channel back the way it was before `application_id`, recorded as a new history entry.
+#[post("/stream-management/history/<application_id>/revert?<provider>")]
+pub async fn revert_stream_management(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    application_id: i32,
+    provider: Option<&str>,
+    global_config: &State<GlobalConfig>,
+) -> Result<(Status, Json<PresetApplication>), Status> {
+    let (platform, user) = get_platform_user(&db_conn, &identity, provider, global_config).await?;
+    let application =
+        find_preset_application(&db_conn, application_id, user.clone(), platform.source()).await?;
+    let restored_state: ChannelState = serde_json::from_value(application.previous_state)
+        .map_err(|_| Status::InternalServerError)?;
+
+    let platform_token = &identity.platform_token.0;
+    let current_state = platform.get_channel(platform_token, &user.user_id).await?;
+
+    let (outcome, saved) = apply_channel_state(
+        &db_conn,
+        platform.as_ref(),
+        platform_token,
+        &user,
+        current_state,
+        restored_state,
+        ApplySource::Revert(application_id),
+    )
+    .await?;
+
+    let status = match outcome.applied {
+        true => Status::Ok,
+        false => Status::BadGateway,
+    };
+
+    Ok((status, Json(PresetApplication::from(saved)?)))
+}
@@ -0,0 +1,353 @@
+use async_trait::async_trait;
+use rocket::http::Status;
+use serde::Serialize;
+
+use crate::{
+    database::favourite_streams::StreamSource,
+    service::{
+        get_channel_information, get_top_games, get_twitch_profile, get_youtube_broadcast,
+        get_youtube_categories, get_youtube_channel, get_youtube_video, modify_channel_information,
+        search_categories, update_youtube_video,
+    },
+    stream_management::{get_access_token, ChannelState, StreamGame},
+    GlobalConfig,
+};
+
+/// Categories are only listed per region on YouTube.
+const YOUTUBE_REGION_CODE: &str = "US";
+/// Pages of top games making up the Twitch catalogue.
+const TWITCH_CATALOGUE_PAGES: usize = 5;
+const TWITCH_MAX_TAGS: usize = 10;
+const TWITCH_MAX_TAG_LENGTH: usize = 25;
+/// YouTube limits the tags together, counting the commas between them and the quotes it puts
+/// around tags with spaces.
+const YOUTUBE_MAX_TAGS_LENGTH: usize = 500;
+
+/// A category a stream can be listed under, a Twitch game or a YouTube video category.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamCategory {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub box_art_url: Option<String>,
+}
+
+/// Everything stream management needs from a streaming platform, presets are applied through it.
+#[async_trait]
+pub trait StreamPlatform: Send + Sync {
+    fn source(&self) -> StreamSource;
+
+    fn max_title_length(&self) -> usize;
+
+    /// `tags` made to fit the platform, dropping any it won't take. Valid tags are left as they are.
+    fn fit_tags(&self, tags: Vec<String>) -> Vec<String>;
+
+    /// The platform's id for whoever `access_token` belongs to, presets are owned by it.
+    async fn get_user_id(&self, access_token: &str) -> Result<String, Status>;
+
+    async fn get_channel(&self, access_token: &str, user_id: &str) -> Result<ChannelState, Status>;
+
+    async fn modify_channel(
+        &self,
+        access_token: &str,
+        user_id: &str,
+        channel_state: &ChannelState,
+    ) -> Result<Status, Status>;
+
+    async fn search_categories(
+        &self,
+        access_token: &str,
+        query: &str,
+    ) -> Result<Vec<StreamCategory>, Status>;
+
+    /// Whether `list_categories` names categories in the requested locale.
+    fn localised(&self) -> bool;
+
+    /// Whether `list_categories` lists every category, prefixes it misses are searched for instead.
+    fn complete_catalogue(&self) -> bool;
+
+    /// Every category offered for autocomplete, named in `locale` where the platform supports it.
+    async fn list_categories(
+        &self,
+        access_token: &str,
+        locale: &str,
+    ) -> Result<Vec<StreamCategory>, Status>;
+}
+
+pub struct TwitchPlatform {
+    pub client_id: String,
+}
+
+#[async_trait]
+impl StreamPlatform for TwitchPlatform {
+    fn source(&self) -> StreamSource {
+        StreamSource::Twitch
+    }
+
+    fn max_title_length(&self) -> usize {
+        140
+    }
+
+    /// Legacy tag names such as "Family Friendly" predate freeform tags, so they're squashed.
+    fn fit_tags(&self, tags: Vec<String>) -> Vec<String> {
+        tags.into_iter()
+            .map(|t| t.split_whitespace().collect::<String>())
+            .filter(|t| !t.is_empty() && t.chars().count() <= TWITCH_MAX_TAG_LENGTH)
+            .take(TWITCH_MAX_TAGS)
+            .collect()
+    }
+
+    async fn get_user_id(&self, access_token: &str) -> Result<String, Status> {
+        let profile = get_twitch_profile(&get_access_token(access_token)).await?;
+        Ok(profile.user_id)
+    }
+
+    async fn get_channel(&self, access_token: &str, user_id: &str) -> Result<ChannelState, Status> {
+        let channel_info = get_channel_information(access_token, user_id, &self.client_id).await?;
+        Ok(ChannelState::from(&channel_info))
+    }
+
+    async fn modify_channel(
+        &self,
+        access_token: &str,
+        user_id: &str,
+        channel_state: &ChannelState,
+    ) -> Result<Status, Status> {
+        modify_channel_information(
+            access_token,
+            user_id,
+            &self.client_id,
+            channel_state.clone().into_modify_request(),
+        )
+        .await
+    }
+
+    async fn search_categories(
+        &self,
+        access_token: &str,
+        query: &str,
+    ) -> Result<Vec<StreamCategory>, Status> {
+        let categories = search_categories(access_token, &self.client_id, query).await?;
+
+        Ok(categories
+            .into_iter()
+            .map(|c| StreamCategory {
+                id: c.id,
+                name: c.name,
+                box_art_url: Some(c.box_art_url),
+            })
+            .collect())
+    }
+
+    fn localised(&self) -> bool {
+        false
+    }
+
+    /// Only the most watched games are listed.
+    fn complete_catalogue(&self) -> bool {
+        false
+    }
+
+    async fn list_categories(
+        &self,
+        access_token: &str,
+        _locale: &str,
+    ) -> Result<Vec<StreamCategory>, Status> {
+        let mut categories = vec![];
+        let mut after = None;
+
+        for _ in 0..TWITCH_CATALOGUE_PAGES {
+            let page = get_top_games(access_token, &self.client_id, after.as_deref()).await?;
+            categories.extend(page.data.into_iter().map(|c| StreamCategory {
+                id: c.id,
+                name: c.name,
+                box_art_url: Some(c.box_art_url),
+            }));
+
+            after = page.pagination.cursor;
+            if after.is_none() {
+                break;
+            }
+        }
+
+        Ok(categories)
+    }
+}
+
+/// Applies to the broadcast that is live, or else the next one scheduled.
+pub struct YoutubePlatform;
+
+#[async_trait]
+impl StreamPlatform for YoutubePlatform {
+    fn source(&self) -> StreamSource {
+        StreamSource::Youtube
+    }
+
+    fn max_title_length(&self) -> usize {
+        100
+    }
+
+    fn fit_tags(&self, tags: Vec<String>) -> Vec<String> {
+        let mut length = 0;
+        tags.into_iter()
+            .map(|t| t.trim().to_owned())
+            .filter(|t| !t.is_empty() && !t.contains(['<', '>']))
+            .take_while(|t| {
+                let quotes = match t.contains(char::is_whitespace) {
+                    true => 2,
+                    false => 0,
+                };
+                // a comma separates every tag after the first
+                length += t.chars().count() + quotes + usize::from(length > 0);
+                length <= YOUTUBE_MAX_TAGS_LENGTH
+            })
+            .collect()
+    }
+
+    async fn get_user_id(&self, access_token: &str) -> Result<String, Status> {
+        let channel = get_youtube_channel(access_token).await?;
+        Ok(channel.id)
+    }
+
+    async fn get_channel(
+        &self,
+        access_token: &str,
+        _user_id: &str,
+    ) -> Result<ChannelState, Status> {
+        let broadcast = get_youtube_broadcast(access_token).await?;
+        let video = get_youtube_video(access_token, &broadcast.id).await?;
+
+        let category =
+            get_youtube_categories(access_token, &format!("id={}", video.snippet.category_id))
+                .await?
+                .into_iter()
+                .next();
+
+        Ok(ChannelState {
+            title: video.snippet.title,
+            tags: video.snippet.tags,
+            game: category.map(|c| StreamGame {
+                id: c.id,
+                name: c.snippet.title,
+            }),
+            broadcaster_language: video.snippet.default_language,
+            description: Some(video.snippet.description),
+        })
+    }
+
+    async fn modify_channel(
+        &self,
+        access_token: &str,
+        _user_id: &str,
+        channel_state: &ChannelState,
+    ) -> Result<Status, Status> {
+        let broadcast = get_youtube_broadcast(access_token).await?;
+        let mut video = get_youtube_video(access_token, &broadcast.id).await?;
+
+        video.snippet.title = channel_state.title.clone();
+        video.snippet.tags = channel_state.tags.clone();
+        if let Some(description) = &channel_state.description {
+            video.snippet.description = description.clone();
+        }
+        if let Some(game) = &channel_state.game {
+            video.snippet.category_id = game.id.clone();
+        }
+        if let Some(language) = &channel_state.broadcaster_language {
+            video.snippet.default_language = Some(language.clone());
+        }
+
+        update_youtube_video(access_token, &video).await
+    }
+
+    async fn search_categories(
+        &self,
+        access_token: &str,
+        query: &str,
+    ) -> Result<Vec<StreamCategory>, Status> {
+        let query = query.to_lowercase();
+        let categories =
+            get_youtube_categories(access_token, &format!("regionCode={}", YOUTUBE_REGION_CODE))
+                .await?;
+
+        Ok(categories
+            .into_iter()
+            .filter(|c| c.snippet.assignable && c.snippet.title.to_lowercase().contains(&query))
+            .map(|c| StreamCategory {
+                id: c.id,
+                name: c.snippet.title,
+                box_art_url: None,
+            })
+            .collect())
+    }
+
+    fn localised(&self) -> bool {
+        true
+    }
+
+    fn complete_catalogue(&self) -> bool {
+        true
+    }
+
+    async fn list_categories(
+        &self,
+        access_token: &str,
+        locale: &str,
+    ) -> Result<Vec<StreamCategory>, Status> {
+        let categories = get_youtube_categories(
+            access_token,
+            &format!("regionCode={}&hl={}", YOUTUBE_REGION_CODE, locale),
+        )
+        .await?;
+
+        Ok(categories
+            .into_iter()
+            .filter(|c| c.snippet.assignable)
+            .map(|c| StreamCategory {
+                id: c.id,
+                name: c.snippet.title,
+                box_art_url: None,
+            })
+            .collect())
+    }
+}
+
+/// Resolves the `provider` a route was called with, Twitch when none is given.
+pub fn stream_platform(
+    provider: Option<&str>,
+    global_config: &GlobalConfig,
+) -> Result<Box<dyn StreamPlatform>, Status> {
+    match provider.map(|p| p.to_lowercase()).as_deref() {
+        None | Some("twitch") => Ok(Box::new(TwitchPlatform {
+            client_id: global_config.twitch_client_id.clone(),
+        })),
+        Some("youtube") => Ok(Box::new(YoutubePlatform)),
+        Some(_) => Err(Status::UnprocessableEntity),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn tags_follow_each_platforms_rules() {
+        let twitch = TwitchPlatform {
+            client_id: "client".to_owned(),
+        };
+        let multi_word = tags(&["Family Friendly", "speed run", "Rust"]);
+
+        assert_eq!(
+            twitch.fit_tags(multi_word.clone()),
+            tags(&["FamilyFriendly", "speedrun", "Rust"])
+        );
+        assert_eq!(YoutubePlatform.fit_tags(multi_word.clone()), multi_word);
+        assert_eq!(
+            YoutubePlatform.fit_tags(vec!["a".repeat(400), "b".repeat(99), "c".to_owned()]),
+            vec!["a".repeat(400), "b".repeat(99)]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// The counter `{counter}` stands for, named counters are written `{counter:deaths}`.
+pub const DEFAULT_COUNTER: &str = "counter";
+const MAX_COUNTER_NAME_LENGTH: usize = 32;
+/// Counters stop here rather than overflow.
+pub const MAX_COUNTER_VALUE: i32 = 999_999_999;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Date,
+    Weekday,
+    Game,
+    Counter(String),
+}
+
+/// A preset title such as "Day {counter} of learning Rust, {weekday}". `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitleTemplate {
+    segments: Vec<Segment>,
+}
+
+/// Everything a template can refer to, taken at the moment a preset is applied.
+#[derive(Debug)]
+pub struct TemplateValues<'a> {
+    pub date: NaiveDate,
+    pub game: Option<&'a str>,
+    pub counters: &'a HashMap<String, i32>,
+}
+
+/// The timezone a title's `{date}` and `{weekday}` are rendered in, an IANA name such as
+/// "America/Denver". Titles without one use UTC, `None` for names that aren't timezones.
+pub fn parse_timezone(timezone: Option<&str>) -> Option<Tz> {
+    match timezone {
+        Some(name) => name.parse().ok(),
+        None => Some(Tz::UTC),
+    }
+}
+
+/// The date it is in `timezone` at `at`, which is what `{date}` and `{weekday}` show.
+pub fn local_date(at: DateTime<Utc>, timezone: Tz) -> NaiveDate {
+    at.with_timezone(&timezone).naive_local().date()
+}
+
+pub fn is_valid_counter_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_COUNTER_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn parse_variable(variable: &str) -> Option<Segment> {
+    match variable {
+        "date" => Some(Segment::Date),
+        "weekday" => Some(Segment::Weekday),
+        "game" => Some(Segment::Game),
+        "counter" => Some(Segment::Counter(DEFAULT_COUNTER.to_owned())),
+        _ => match variable.strip_prefix("counter:") {
+            Some(name) if is_valid_counter_name(name) => Some(Segment::Counter(name.to_owned())),
+            _ => None,
+        },
+    }
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+impl TitleTemplate {
+    /// `None` for unknown variables and unbalanced braces.
+    pub fn parse(template: &str) -> Option<Self> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut variable = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return None,
+                            Some(c) => variable.push(c),
+                        }
+                    }
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(parse_variable(&variable)?);
+                }
+                '}' => return None,
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Some(Self { segments })
+    }
+
+    /// Names of the counters used, each once, these are the ones bumped when the preset is applied.
+    pub fn counters(&self) -> Vec<String> {
+        let mut counters: Vec<String> = vec![];
+        for segment in &self.segments {
+            if let Segment::Counter(name) = segment {
+                if !counters.contains(name) {
+                    counters.push(name.clone());
+                }
+            }
+        }
+        counters
+    }
+
+    /// Counters without a value render as 0, game as empty when there's none.
+    pub fn render(&self, values: &TemplateValues) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Date => values.date.format("%Y-%m-%d").to_string(),
+                Segment::Weekday => weekday_name(values.date.weekday()).to_owned(),
+                Segment::Game => values.game.unwrap_or_default().to_owned(),
+                Segment::Counter(name) => values
+                    .counters
+                    .get(name)
+                    .copied()
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn renders_every_variable() {
+        let template = TitleTemplate::parse(
+            "{weekday} {date}: Day {counter} of {game}, {counter:deaths} deaths {{}}",
+        )
+        .unwrap();
+        let counters = HashMap::from([("counter".to_owned(), 14)]);
+
+        let rendered = template.render(&TemplateValues {
+            date: NaiveDate::from_ymd(2026, 10, 18),
+            game: Some("Elden Ring"),
+            counters: &counters,
+        });
+
+        assert_eq!(
+            rendered,
+            "Sunday 2026-10-18: Day 14 of Elden Ring, 0 deaths {}"
+        );
+        assert_eq!(template.counters(), vec!["counter", "deaths"]);
+    }
+
+    #[test]
+    fn dates_follow_the_timezone() {
+        let evening_in_denver = Utc.ymd(2026, 10, 19).and_hms(2, 0, 0);
+
+        assert_eq!(
+            local_date(evening_in_denver, parse_timezone(None).unwrap()),
+            NaiveDate::from_ymd(2026, 10, 19)
+        );
+        assert_eq!(
+            local_date(
+                evening_in_denver,
+                parse_timezone(Some("America/Denver")).unwrap()
+            ),
+            NaiveDate::from_ymd(2026, 10, 18)
+        );
+        assert_eq!(parse_timezone(Some("Mars/Olympus_Mons")), None);
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in [
+            "Day {counter",
+            "Day counter}",
+            "{streak}",
+            "{counter:Deaths}",
+            "{}",
+        ] {
+            assert_eq!(TitleTemplate::parse(template), None, "{}", template);
+        }
+        assert!(TitleTemplate::parse("No variables at all").is_some());
+    }
+}
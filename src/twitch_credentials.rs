@@ -0,0 +1,370 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use chrono::{Duration, Utc};
+use cookie::{Cookie, CookieJar, Key};
+use rocket::{
+    delete,
+    figment::Figment,
+    get,
+    http::{RawStr, Status},
+    post,
+    serde::json::Json,
+    tokio::sync::Mutex,
+    warn, State,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::{
+    authenticate::{PlatformAccessToken, StreamIdentity},
+    database::favourite_streams::StreamSource,
+    database::linked_account::{link_account, LinkedAccountModel},
+    database::twitch_credential::{
+        delete_twitch_credential, find_twitch_credential, upsert_twitch_credential,
+        TwitchCredentialModel,
+    },
+    service::{
+        exchange_authorization_code, get_twitch_profile, refresh_user_token, TwitchUserToken,
+    },
+    stream_management::{get_access_token, get_user},
+    DbConn, GlobalConfig,
+};
+
+const TWITCH_SCOPES: &str = "channel:manage:broadcast user:read:follows";
+const LINK_STATE_NAME: &str = "twitch-link-state";
+const LINK_STATE_TTL: i64 = 10 * 60;
+/// Tokens are refreshed a little early so they can't expire mid request.
+const EXPIRY_MARGIN: i64 = 60;
+
+/// Rocket's `secret_key` as raw bytes, accepting what Rocket does: a 256-bit base64 or hex string,
+/// or the bytes themselves. Rocket fills in zeroes when no key is configured.
+struct SecretKeyBytes(Vec<u8>);
+
+impl<'de> Deserialize<'de> for SecretKeyBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = SecretKeyBytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("256-bit base64 or hex string, or bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<SecretKeyBytes, E> {
+                let invalid = || E::invalid_value(de::Unexpected::Str(value), &self);
+                let bytes = match value.len() {
+                    44 | 88 => base64::decode(value).map_err(|_| invalid())?,
+                    64 => (0..value.len())
+                        .step_by(2)
+                        .map(|i| {
+                            value
+                                .get(i..i + 2)
+                                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                        })
+                        .collect::<Option<Vec<u8>>>()
+                        .ok_or_else(invalid)?,
+                    _ => return Err(invalid()),
+                };
+                Ok(SecretKeyBytes(bytes))
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<SecretKeyBytes, E> {
+                Ok(SecretKeyBytes(value.to_vec()))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<SecretKeyBytes, A::Error> {
+                let mut bytes = vec![];
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(SecretKeyBytes(bytes))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Encrypts stored Twitch tokens with the same `secret_key` Rocket uses for private cookies.
+#[derive(Clone)]
+pub struct TwitchCredentials {
+    key: Key,
+    refresh_locks: Arc<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+}
+
+impl TwitchCredentials {
+    /// Reads `secret_key` from Rocket's config, without one tokens won't survive a restart.
+    pub fn from(figment: &Figment) -> Self {
+        let secret_key = figment
+            .extract_inner::<Option<SecretKeyBytes>>(rocket::Config::SECRET_KEY)
+            .expect("secret_key must be a 256-bit base64 or hex string")
+            .map(|secret_key| secret_key.0)
+            .unwrap_or_default();
+
+        let key = match secret_key.len() {
+            _ if secret_key.iter().all(|b| *b == 0) => {
+                warn!("no secret_key configured, linked Twitch accounts won't survive a restart");
+                Key::generate()
+            }
+            n if n >= 64 => Key::from(&secret_key),
+            n if n >= 32 => Key::derive_from(&secret_key),
+            _ => panic!("secret_key must be at least 256 bits"),
+        };
+
+        Self {
+            key,
+            refresh_locks: Arc::default(),
+        }
+    }
+
+    /// One refresh per user at a time, locks nobody is waiting on are dropped along the way.
+    fn refresh_lock(&self, user_id: &str) -> Arc<Mutex<()>> {
+        let mut refresh_locks = self.refresh_locks.lock().unwrap();
+        refresh_locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        refresh_locks.entry(user_id.to_owned()).or_default().clone()
+    }
+
+    /// `name` is authenticated along with the value, so a ciphertext only decrypts under the same name.
+    fn encrypt(&self, name: &str, value: &str) -> String {
+        let mut jar = CookieJar::new();
+        jar.private_mut(&self.key)
+            .add(Cookie::new(name.to_owned(), value.to_owned()));
+
+        jar.get(name).unwrap().value().to_owned()
+    }
+
+    fn decrypt(&self, name: &str, value: &str) -> Option<String> {
+        CookieJar::new()
+            .private(&self.key)
+            .decrypt(Cookie::new(name.to_owned(), value.to_owned()))
+            .map(|c| c.value().to_owned())
+    }
+
+    fn token_name(user_id: &str) -> String {
+        format!("twitch-credential-{}", user_id)
+    }
+}
+
+/// Confirms the tokens belong to `user_id` and saves them, returning the usable `Bearer` token.
+async fn store_user_token(
+    db_conn: &DbConn,
+    credentials: &TwitchCredentials,
+    user_id: &str,
+    token: TwitchUserToken,
+) -> Result<String, Status> {
+    let bearer = format!("Bearer {}", token.access_token);
+    let profile = get_twitch_profile(&get_access_token(&bearer)).await?;
+    if profile.user_id != user_id {
+        return Err(Status::Forbidden);
+    }
+
+    let name = TwitchCredentials::token_name(user_id);
+    let credential = TwitchCredentialModel {
+        associated_user: user_id.to_owned(),
+        access_token: credentials.encrypt(&name, &token.access_token),
+        refresh_token: credentials.encrypt(&name, &token.refresh_token),
+        expires_at: Utc::now() + Duration::seconds(i64::from(profile.expires_in)),
+    };
+    upsert_twitch_credential(db_conn, credential).await?;
+
+    Ok(bearer)
+}
+
+/// The stored access token while it's still valid.
+fn stored_access_token(
+    credentials: &TwitchCredentials,
+    credential: &TwitchCredentialModel,
+) -> Option<String> {
+    if credential.expires_at - Duration::seconds(EXPIRY_MARGIN) <= Utc::now() {
+        return None;
+    }
+    credentials
+        .decrypt(
+            &TwitchCredentials::token_name(&credential.associated_user),
+            &credential.access_token,
+        )
+        .map(|access_token| format!("Bearer {}", access_token))
+}
+
+/// Returns a valid `Bearer` token for a linked user, refreshing it once it's about to expire.
+/// `PreconditionRequired` means the user has to link their account first.
+pub async fn get_twitch_access_token(
+    db_conn: &DbConn,
+    credentials: &TwitchCredentials,
+    global_config: &GlobalConfig,
+    user_id: &str,
+) -> Result<String, Status> {
+    let credential = find_twitch_credential(db_conn, user_id.to_owned())
+        .await?
+        .ok_or(Status::PreconditionRequired)?;
+    if let Some(access_token) = stored_access_token(credentials, &credential) {
+        return Ok(access_token);
+    }
+
+    // Twitch invalidates a refresh token once it has been used, so whoever waited on the lock
+    // picks up the token the first refresh stored
+    let refresh_lock = credentials.refresh_lock(user_id);
+    let _refreshing = refresh_lock.lock().await;
+
+    let credential = find_twitch_credential(db_conn, user_id.to_owned())
+        .await?
+        .ok_or(Status::PreconditionRequired)?;
+    if let Some(access_token) = stored_access_token(credentials, &credential) {
+        return Ok(access_token);
+    }
+    let name = TwitchCredentials::token_name(user_id);
+
+    let refreshed = match credentials.decrypt(&name, &credential.refresh_token) {
+        Some(refresh_token) => {
+            refresh_user_token(
+                &refresh_token,
+                &global_config.twitch_client_id,
+                &global_config.twitch_client_secret,
+            )
+            .await
+        }
+        // the secret key changed since the account was linked
+        None => Err(Status::Unauthorized),
+    };
+
+    match refreshed {
+        Ok(token) => store_user_token(db_conn, credentials, user_id, token).await,
+        Err(status) if status == Status::Unauthorized => {
+            delete_twitch_credential(db_conn, user_id.to_owned()).await?;
+            Err(Status::PreconditionRequired)
+        }
+        Err(status) => Err(status),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwitchLinkResponse {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwitchLinkRequest {
+    pub code: String,
+    pub state: String,
+}
+
+/// Starts linking, the client sends the user to `url` and posts the code Twitch redirects back with.
+#[get("/stream-management/twitch-link")]
+pub async fn get_twitch_link(
+    platform_token: PlatformAccessToken,
+    credentials: &State<TwitchCredentials>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Json<TwitchLinkResponse>, Status> {
+    let profile = get_user(&platform_token).await?;
+
+    let state = credentials.encrypt(
+        LINK_STATE_NAME,
+        &format!("{}:{}", profile.user_id, Utc::now().timestamp()),
+    );
+    let url = format!(
+        "https://id.twitch.tv/oauth2/authorize?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}",
+        global_config.twitch_client_id,
+        RawStr::new(&global_config.twitch_redirect_uri).percent_encode(),
+        RawStr::new(TWITCH_SCOPES).percent_encode(),
+        RawStr::new(&state).percent_encode()
+    );
+
+    Ok(Json(TwitchLinkResponse { url }))
+}
+
+/// Finishes linking, also linking the Twitch account to the caller's profile when they sent their
+/// BeemStream token, see `StreamIdentity`.
+#[post("/stream-management/twitch-link", data = "<twitch_link_request>")]
+pub async fn post_twitch_link(
+    db_conn: DbConn,
+    identity: StreamIdentity,
+    twitch_link_request: Json<TwitchLinkRequest>,
+    credentials: &State<TwitchCredentials>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, Status> {
+    let profile = get_user(&identity.platform_token).await?;
+    let request = twitch_link_request.into_inner();
+
+    let state = credentials
+        .decrypt(LINK_STATE_NAME, &request.state)
+        .ok_or(Status::BadRequest)?;
+    let (user_id, issued_at) = state.split_once(':').ok_or(Status::BadRequest)?;
+    let issued_at: i64 = issued_at.parse().map_err(|_| Status::BadRequest)?;
+    if user_id != profile.user_id || Utc::now().timestamp() - issued_at > LINK_STATE_TTL {
+        return Err(Status::BadRequest);
+    }
+
+    let token = exchange_authorization_code(
+        &request.code,
+        &global_config.twitch_client_id,
+        &global_config.twitch_client_secret,
+        &global_config.twitch_redirect_uri,
+    )
+    .await?;
+    if let Some(profile_id) = identity.profile_id {
+        link_account(
+            &db_conn,
+            LinkedAccountModel {
+                associated_profile: profile_id,
+                provider: StreamSource::Twitch,
+                platform_user_id: profile.user_id.clone(),
+            },
+        )
+        .await?;
+    }
+    store_user_token(&db_conn, credentials, &profile.user_id, token).await?;
+
+    Ok(Status::NoContent)
+}
+
+#[delete("/stream-management/twitch-link")]
+pub async fn delete_twitch_link(
+    db_conn: DbConn,
+    platform_token: PlatformAccessToken,
+) -> Result<Status, Status> {
+    let profile = get_user(&platform_token).await?;
+
+    match delete_twitch_credential(&db_conn, profile.user_id).await? > 0 {
+        true => Ok(Status::NoContent),
+        false => Err(Status::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(secret_key: &str) -> TwitchCredentials {
+        TwitchCredentials::from(&rocket::Config::figment().merge(("secret_key", secret_key)))
+    }
+
+    #[test]
+    fn tokens_only_decrypt_for_the_same_user() {
+        let credentials = credentials("hPRYyVRiMyxpw5sBB1XeCMN1kFsDCqKvBi2QJxBVHQk=");
+        let owner = TwitchCredentials::token_name("owner");
+        let intruder = TwitchCredentials::token_name("intruder");
+
+        let encrypted = credentials.encrypt(&owner, "refresh-token");
+
+        assert_ne!(encrypted, "refresh-token");
+        assert_eq!(
+            credentials.decrypt(&owner, &encrypted).as_deref(),
+            Some("refresh-token")
+        );
+        assert_eq!(credentials.decrypt(&intruder, &encrypted), None);
+    }
+
+    #[test]
+    fn reads_secret_key_like_rocket_does() {
+        let base64 = credentials("hPRYyVRiMyxpw5sBB1XeCMN1kFsDCqKvBi2QJxBVHQk=");
+        let hex = credentials("84f458c95462332c69c39b010755de08c375905b030aa2af062d902710551d09");
+        let encrypted = base64.encrypt("name", "value");
+
+        assert_eq!(hex.decrypt("name", &encrypted).as_deref(), Some("value"));
+        // Rocket's own default when no key is configured
+        TwitchCredentials::from(&rocket::Config::figment());
+    }
+}